@@ -2,18 +2,33 @@ use std::env;
 use std::fs;
 use std::path::Path;
 
+// Shell scripts embedded into the binary, as (file, constant name)
+const SHELL_SCRIPTS: &[(&str, &str)] = &[
+    ("ccd.sh", "CCD_SHELL_FUNCTION"),
+    ("ccd.zsh", "CCD_ZSH_FUNCTION"),
+    ("ccd.fish", "CCD_FISH_FUNCTION"),
+    ("ccd.nu", "CCD_NUSHELL_FUNCTION"),
+    ("ccd-track.sh", "CCD_TRACK_HOOK"),
+    ("ccd-track.fish", "CCD_FISH_TRACK_HOOK"),
+    ("ccd-track.nu", "CCD_NUSHELL_TRACK_HOOK"),
+];
+
 fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("ccd_shell_function.rs");
 
-    // Read the shell script
-    let shell_script = fs::read_to_string("ccd.sh").expect("Failed to read ccd.sh");
+    // Generate Rust code that includes each shell script as a string constant
+    let mut rust_code = String::new();
+    for (file, name) in SHELL_SCRIPTS {
+        let shell_script =
+            fs::read_to_string(file).unwrap_or_else(|_| panic!("Failed to read {file}"));
+        rust_code.push_str(&format!(
+            "pub const {name}: &str = r#\"{shell_script}\"#;\n"
+        ));
 
-    // Generate Rust code that includes the shell script as a string constant
-    let rust_code = format!("pub const CCD_SHELL_FUNCTION: &str = r#\"{shell_script}\"#;");
+        // Tell cargo to rerun if the script changes
+        println!("cargo:rerun-if-changed={file}");
+    }
 
     fs::write(&dest_path, rust_code).expect("Failed to write shell function");
-
-    // Tell cargo to rerun if ccd.sh changes
-    println!("cargo:rerun-if-changed=ccd.sh");
 }
@@ -0,0 +1,11 @@
+#!/usr/bin/env fish
+
+# Hook that records every directory change with ccd-pick --track
+# Printed after the ccd function by: ccd-pick init fish --hook prompt
+
+# Runs only when PWD changes, so repeated prompts in one directory aren't counted. Recording
+# runs in the background and is disowned, so it never delays the prompt or reports its end.
+function __ccd_track --on-variable PWD
+    ccd-pick --track "$PWD" >/dev/null 2>&1 &
+    disown
+end
@@ -0,0 +1,23 @@
+#!/bin/bash
+
+# Hook that records every directory change with ccd-pick --track
+# Printed after the ccd function by: ccd-pick init <bash|zsh> --hook prompt
+
+__ccd_track() {
+    # Only record once per directory, not on every prompt. Recording runs in the background,
+    # in a subshell so the shell doesn't report the job, and never delays the prompt.
+    if [ "$PWD" != "${__ccd_last_pwd:-}" ]; then
+        __ccd_last_pwd=$PWD
+        (ccd-pick --track "$PWD" >/dev/null 2>&1 &)
+    fi
+}
+
+if [ -n "${ZSH_VERSION:-}" ]; then
+    autoload -Uz add-zsh-hook
+    add-zsh-hook chpwd __ccd_track
+elif [ -n "${BASH_VERSION:-}" ]; then
+    case ";${PROMPT_COMMAND:-};" in
+        *";__ccd_track;"*) ;;
+        *) PROMPT_COMMAND="__ccd_track${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+    esac
+fi
@@ -0,0 +1,61 @@
+#!/usr/bin/env fish
+
+# fish wrapper for the ccd-pick command
+# This function is loaded from ~/.config/fish/conf.d/ccd.fish with: ccd-pick --printfn fish | source
+
+function {{cmd}} --description 'Change directory with ccd-pick'
+    # Show brief help for ccd command itself
+    if contains -- "$argv[1]" --help -h
+        echo "{{cmd}} - Change Change Directory"
+        echo ""
+        echo "USAGE:"
+        echo "    {{cmd}}                    Enter interactive directory picker"
+        echo "    {{cmd}} -i                 Enter interactive directory picker"
+        echo "    {{cmd}} -b [name]          Bookmark current directory as @name"
+        echo "    {{cmd}} @name              Change to a bookmarked directory"
+        echo "    {{cmd}} <pattern>...       Search and change to directory matching all terms"
+        echo "    {{cmd}} -- <pattern>...    Same, for patterns starting with - or @"
+        echo ""
+        echo "This wrapper calls the ccd-pick tool. For more detailed help, use: ccd-pick --help"
+        return
+    end
+
+    if test (count $argv) -eq 0; or test "$argv[1]" = -i
+        # No arguments or "-i" - enter interactive mode. The TUI draws on the terminal and
+        # writes the selected directory to file descriptor 3, which is captured here.
+        set -l output (ccd-pick -i 3>&1 >/dev/tty 2>&1)
+        set -l exit_code $status
+
+        if test $exit_code -eq 0; and test -n "$output"; and test -d "$output"
+            # Successfully selected a directory, change to it
+            cd -- "$output"
+            echo "Changed to: $output"
+        else if test $exit_code -eq 1
+            # User quit without selecting, don't change directory
+            echo "Selection cancelled"
+        end
+        return
+    else if test "$argv[1]" = -b
+        # Bookmark current directory
+        ccd-pick $argv
+        return
+    end
+
+    # Words are search terms, even ones that are ccd-pick commands such as "list"
+    if not string match -q -r -- '^[-@]' $argv[1]
+        set -- argv -- $argv
+    end
+
+    # Capture the output from the ccd binary
+    set -l output (ccd-pick $argv 2>/dev/null)
+    set -l exit_code $status
+
+    if test $exit_code -eq 0; and test -n "$output"; and test -d "$output"
+        # Successfully found a directory, change to it
+        cd -- "$output"
+        echo "Changed to: $output"
+    else
+        # Show error output or help
+        ccd-pick $argv
+    end
+end
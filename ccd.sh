@@ -3,16 +3,18 @@
 # Shell wrapper for the ccd-pick command
 # This function should be sourced in your shell profile (e.g., ~/.bashrc or ~/.zshrc)
 
-ccd() {
+{{cmd}}() {
     # Show brief help for ccd command itself
     if [ "$1" = "--help" ] || [ "$1" = "-h" ]; then
-        echo "ccd - Change Change Directory"
+        echo "{{cmd}} - Change Change Directory"
         echo ""
         echo "USAGE:"
-        echo "    ccd                    Enter interactive directory picker"
-        echo "    ccd -i                 Enter interactive directory picker"
-        echo "    ccd -b                 Bookmark current directory"
-        echo "    ccd <pattern>          Search and change to directory matching pattern"
+        echo "    {{cmd}}                    Enter interactive directory picker"
+        echo "    {{cmd}} -i                 Enter interactive directory picker"
+        echo "    {{cmd}} -b [name]          Bookmark current directory as @name"
+        echo "    {{cmd}} @name              Change to a bookmarked directory"
+        echo "    {{cmd}} <pattern>...       Search and change to directory matching all terms"
+        echo "    {{cmd}} -- <pattern>...    Same, for patterns starting with - or @"
         echo ""
         echo "This wrapper calls the ccd-pick tool. For more detailed help, use: ccd-pick --help"
         return
@@ -38,10 +40,16 @@ ccd() {
         return
     elif [ "$1" = "-b" ]; then
         # Bookmark current directory
-        ccd-pick -b
+        ccd-pick "$@"
         return
     fi
 
+    # Words are search terms, even ones that are ccd-pick commands such as "list"
+    case "$1" in
+        -* | @*) ;;
+        *) set -- -- "$@" ;;
+    esac
+
     # Capture the output from the ccd binary
     local output
     output=$(ccd-pick "$@" 2>/dev/null)
@@ -0,0 +1,609 @@
+// Pluggable sources of candidate directories for DirectorySearcher
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::exclude::Exclusions;
+use crate::locatedb::{self, Candidate, Cursor, DatabaseMatches, LocateDatabase, SCAN_BUDGET};
+use crate::matcher::Matcher;
+use crate::walker::{self, WalkRest, WalkResult, WalkerConfig};
+use crate::{CddError, FrequencyMap};
+
+const LOCATE_DATABASES: &[&str] = &[
+    "/var/lib/plocate/plocate.db",
+    "/var/lib/mlocate/mlocate.db",
+    "/var/cache/locate/locatedb",
+    "/var/lib/locate/locatedb",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    Locate,
+    Frequency,
+    Walk,
+    Fd,
+    Find,
+    Command,
+}
+
+impl BackendKind {
+    pub fn parse(name: &str) -> Result<Self, CddError> {
+        match name {
+            "locate" => Ok(BackendKind::Locate),
+            "frequency" => Ok(BackendKind::Frequency),
+            "walk" => Ok(BackendKind::Walk),
+            "fd" => Ok(BackendKind::Fd),
+            "find" => Ok(BackendKind::Find),
+            "command" => Ok(BackendKind::Command),
+            other => Err(CddError::Config(format!(
+                "unknown search backend '{other}' (expected locate, frequency, walk, fd, find or command)"
+            ))),
+        }
+    }
+}
+
+// One entry of the configured backend list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendSpec {
+    pub kind: BackendKind,
+    // Fallback backends only run when earlier ones found nothing, failed or were stale
+    pub fallback: bool,
+}
+
+impl BackendSpec {
+    // Parse a comma-separated list such as "locate,walk?,frequency"
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, CddError> {
+        let specs = value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let (name, fallback) = match name.strip_suffix('?') {
+                    Some(name) => (name, true),
+                    None => (name, false),
+                };
+                BackendKind::parse(name).map(|kind| BackendSpec { kind, fallback })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if specs.is_empty() {
+            return Err(CddError::Config(
+                "no search backends configured".to_string(),
+            ));
+        }
+        Ok(specs)
+    }
+
+    // The single-backend CCD_BACKEND setting of earlier versions, still accepted when
+    // CCD_BACKENDS isn't set: auto, locate or walk
+    pub fn parse_legacy(value: &str) -> Result<Vec<Self>, CddError> {
+        let list = match value.trim() {
+            "auto" => return Ok(Self::default_list()),
+            "locate" => "locate,frequency",
+            "walk" => "walk,frequency",
+            other => {
+                return Err(CddError::Config(format!(
+                    "unknown search backend '{other}' (expected auto, locate or walk)"
+                )))
+            }
+        };
+        Self::parse_list(list)
+    }
+
+    pub fn default_list() -> Vec<Self> {
+        vec![
+            BackendSpec {
+                kind: BackendKind::Locate,
+                fallback: false,
+            },
+            BackendSpec {
+                kind: BackendKind::Walk,
+                fallback: true,
+            },
+            BackendSpec {
+                kind: BackendKind::Frequency,
+                fallback: false,
+            },
+        ]
+    }
+}
+
+#[derive(Default)]
+pub struct BackendOutput {
+    pub directories: Vec<String>,
+    pub files_filtered: usize,
+    // Set when results may be incomplete (e.g. a stale locate database), so fallbacks run
+    pub degraded: bool,
+    // Set when the backend stopped at the directory limit and more results may exist
+    pub rest: Option<Box<dyn Continuation>>,
+}
+
+impl BackendOutput {
+    // Add a candidate path, keeping directories and counting everything else as a filtered
+    // file. Returns false once `limit` directories have been collected.
+    fn push_candidate(&mut self, path: String, limit: usize) -> bool {
+        if Path::new(&path).is_dir() {
+            self.directories.push(path);
+        } else {
+            self.files_filtered += 1;
+        }
+        self.directories.len() < limit
+    }
+}
+
+// The rest of a search that stopped at the directory limit, so that loading more results
+// carries on from there instead of searching again
+pub trait Continuation {
+    // Find up to `limit` more directories, stopping early once `cancel` is set
+    fn next(self: Box<Self>, limit: usize, cancel: &AtomicBool) -> Result<BackendOutput, CddError>;
+}
+
+pub trait SearchBackend {
+    fn name(&self) -> &'static str;
+    // Find up to `limit` directories matching `matcher` that aren't excluded, returning how to continue in `rest`
+    // when stopping at the limit. Long-running backends should stop early and return what
+    // they have once `cancel` is set.
+    fn search(
+        &self,
+        matcher: &Matcher,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError>;
+}
+
+// A running command whose output lines are read as candidates until enough directories
+// were found. The command then waits on its full pipe until more are wanted, and is
+// stopped when the search is dropped.
+struct CommandOutput {
+    child: Child,
+    reader: BufReader<ChildStdout>,
+    // Turns a non-empty output line into a candidate path, or None to skip it
+    candidate: Box<dyn Fn(String) -> Option<String>>,
+}
+
+impl CommandOutput {
+    fn spawn(
+        mut command: Command,
+        candidate: impl Fn(String) -> Option<String> + 'static,
+    ) -> io::Result<Box<Self>> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let Some(stdout) = child.stdout.take() else {
+            let _ = child.kill();
+            return Err(io::Error::other("command output is not readable"));
+        };
+        Ok(Box::new(Self {
+            child,
+            reader: BufReader::new(stdout),
+            candidate: Box::new(candidate),
+        }))
+    }
+
+    // The next non-empty output line, or None at the end of the output. Paths aren't
+    // necessarily UTF-8, so invalid bytes are replaced rather than failing the search.
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(None);
+            }
+            while line.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+                line.pop();
+            }
+            if !line.is_empty() {
+                return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+            }
+        }
+    }
+}
+
+impl Continuation for CommandOutput {
+    fn next(
+        mut self: Box<Self>,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError> {
+        let mut result = BackendOutput::default();
+        while !cancel.load(Ordering::Relaxed) {
+            let Some(line) = self.read_line()? else {
+                return Ok(result);
+            };
+            if let Some(path) = (self.candidate)(line) {
+                if !result.push_candidate(path, limit) {
+                    break;
+                }
+            }
+        }
+        result.rest = Some(self);
+        Ok(result)
+    }
+}
+
+impl Drop for CommandOutput {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Searches the locate database, reading it directly when it is readable and falling back
+// to the `locate` command otherwise
+pub struct LocateBackend<'a> {
+    pub max_age: Duration,
+    pub database: Option<&'a PathBuf>,
+    pub exclude: &'a Exclusions,
+}
+
+impl LocateBackend<'_> {
+    // Age of the newest locate database found, if any
+    fn database_age() -> Option<Duration> {
+        LOCATE_DATABASES
+            .iter()
+            .filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+            .max()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+    }
+}
+
+impl SearchBackend for LocateBackend<'_> {
+    fn name(&self) -> &'static str {
+        "locate"
+    }
+
+    fn search(
+        &self,
+        matcher: &Matcher,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError> {
+        if let Ok(database) = LocateDatabase::shared(self.database.map(PathBuf::as_path)) {
+            let matches = database.search(matcher, self.exclude, limit, cancel)?;
+            let mut output =
+                DatabaseSearch::output(database.clone(), matcher, self.exclude, matches);
+            output.degraded = database.age().is_some_and(|age| age > self.max_age);
+            return Ok(output);
+        }
+
+        // The regex only covers one term of the query, so scoring checks all of them
+        let mut command = Command::new("locate");
+        command
+            .args(matcher.ignore_case().then_some("--ignore-case"))
+            .arg("--regex")
+            .arg(matcher.to_posix_regex());
+        let exclude = self.exclude.clone();
+        let output = CommandOutput::spawn(command, move |line| {
+            (!exclude.is_excluded(&line)).then_some(line)
+        })
+        .map_err(|e| CddError::LocateCommand(format!("Failed to execute locate: {e}")))?;
+
+        let ranked = Box::new(RankedOutput {
+            output: Some(output),
+            matcher: matcher.clone(),
+            candidates: Vec::new(),
+        });
+        let mut result = ranked.next(limit, cancel)?;
+        result.degraded = Self::database_age().is_some_and(|age| age > self.max_age);
+        Ok(result)
+    }
+}
+
+// Output of the `locate` command, ranked like a search of the database itself: everything it
+// prints is scored before the best directories are returned, reading for at most SCAN_BUDGET
+// at a time
+struct RankedOutput {
+    // The command, until its output has been read in full
+    output: Option<Box<CommandOutput>>,
+    matcher: Matcher,
+    // Matches read but not returned yet
+    candidates: Vec<Candidate>,
+}
+
+impl Continuation for RankedOutput {
+    fn next(
+        mut self: Box<Self>,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError> {
+        let deadline = Instant::now() + SCAN_BUDGET;
+        while let Some(output) = &mut self.output {
+            if cancel.load(Ordering::Relaxed) || Instant::now() >= deadline {
+                break;
+            }
+            let Some(line) = output.read_line()? else {
+                self.output = None;
+                break;
+            };
+            let Some(path) = (output.candidate)(line) else {
+                continue;
+            };
+            if let Some(found) = self.matcher.score(&path) {
+                self.candidates
+                    .push((found.score, Reverse(path.len()), path));
+            }
+        }
+
+        let mut result = BackendOutput::default();
+        self.candidates = locatedb::take_best(
+            std::mem::take(&mut self.candidates),
+            limit,
+            &mut result.directories,
+            &mut result.files_filtered,
+        );
+        if self.output.is_some() || !self.candidates.is_empty() {
+            result.rest = Some(self);
+        }
+        Ok(result)
+    }
+}
+
+// The rest of a search of the locate database
+struct DatabaseSearch {
+    database: Arc<LocateDatabase>,
+    matcher: Matcher,
+    exclude: Exclusions,
+    cursor: Cursor,
+}
+
+impl DatabaseSearch {
+    fn output(
+        database: Arc<LocateDatabase>,
+        matcher: &Matcher,
+        exclude: &Exclusions,
+        matches: DatabaseMatches,
+    ) -> BackendOutput {
+        BackendOutput {
+            directories: matches.directories,
+            files_filtered: matches.files_filtered,
+            degraded: false,
+            rest: matches.rest.map(|cursor| {
+                Box::new(DatabaseSearch {
+                    database,
+                    matcher: matcher.clone(),
+                    exclude: exclude.clone(),
+                    cursor,
+                }) as Box<dyn Continuation>
+            }),
+        }
+    }
+}
+
+impl Continuation for DatabaseSearch {
+    fn next(self: Box<Self>, limit: usize, cancel: &AtomicBool) -> Result<BackendOutput, CddError> {
+        let matches =
+            self.database
+                .resume(&self.matcher, &self.exclude, self.cursor, limit, cancel)?;
+        Ok(Self::output(
+            self.database,
+            &self.matcher,
+            &self.exclude,
+            matches,
+        ))
+    }
+}
+
+// Matches against directories already recorded in the frequency file
+pub struct FrequencyBackend<'a> {
+    pub frequency_map: &'a FrequencyMap,
+    pub exclude: &'a Exclusions,
+}
+
+impl SearchBackend for FrequencyBackend<'_> {
+    fn name(&self) -> &'static str {
+        "frequency"
+    }
+
+    fn search(
+        &self,
+        matcher: &Matcher,
+        _limit: usize,
+        _cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError> {
+        let directories = self
+            .frequency_map
+            .keys()
+            .filter(|path| {
+                matcher.is_match(path)
+                    && !self.exclude.is_excluded(path)
+                    && Path::new(path).is_dir()
+            })
+            .cloned()
+            .collect();
+
+        Ok(BackendOutput {
+            directories,
+            ..BackendOutput::default()
+        })
+    }
+}
+
+// Walks the filesystem with the built-in parallel walker
+pub struct WalkBackend<'a> {
+    pub config: &'a WalkerConfig,
+}
+
+impl SearchBackend for WalkBackend<'_> {
+    fn name(&self) -> &'static str {
+        "walk"
+    }
+
+    fn search(
+        &self,
+        matcher: &Matcher,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError> {
+        let result = walker::walk(matcher, self.config, limit, cancel);
+        Ok(Walk::output(matcher, self.config, result))
+    }
+}
+
+// The rest of a filesystem walk
+struct Walk {
+    matcher: Matcher,
+    config: WalkerConfig,
+    rest: WalkRest,
+}
+
+impl Walk {
+    fn output(matcher: &Matcher, config: &WalkerConfig, result: WalkResult) -> BackendOutput {
+        BackendOutput {
+            directories: result.directories,
+            files_filtered: result.files_matched,
+            degraded: false,
+            rest: result.rest.map(|rest| {
+                Box::new(Walk {
+                    matcher: matcher.clone(),
+                    config: config.clone(),
+                    rest,
+                }) as Box<dyn Continuation>
+            }),
+        }
+    }
+}
+
+impl Continuation for Walk {
+    fn next(self: Box<Self>, limit: usize, cancel: &AtomicBool) -> Result<BackendOutput, CddError> {
+        let Walk {
+            matcher,
+            config,
+            rest,
+        } = *self;
+        let result = walker::resume(&matcher, &config, rest, limit, cancel);
+        Ok(Walk::output(&matcher, &config, result))
+    }
+}
+
+// Uses `fd` (installed as `fdfind` on Debian and Ubuntu) over the walker roots
+pub struct FdBackend<'a> {
+    pub config: &'a WalkerConfig,
+}
+
+impl SearchBackend for FdBackend<'_> {
+    fn name(&self) -> &'static str {
+        "fd"
+    }
+
+    fn search(
+        &self,
+        matcher: &Matcher,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError> {
+        for program in ["fd", "fdfind"] {
+            let mut command = Command::new(program);
+            command
+                .args(["--type", "d", "--full-path", "--absolute-path"])
+                // fd uses smart case by default, which the matcher has already decided on
+                .arg(if matcher.ignore_case() {
+                    "--ignore-case"
+                } else {
+                    "--case-sensitive"
+                })
+                .arg("--max-depth")
+                .arg(self.config.max_depth.to_string())
+                .arg("--")
+                .arg(matcher.to_regex())
+                .args(&self.config.roots);
+
+            let matcher = matcher.clone();
+            let exclude = self.config.exclude.clone();
+            let output = CommandOutput::spawn(command, move |line| {
+                // fd prints directories with a trailing slash
+                let path = match line.strip_suffix('/') {
+                    Some(trimmed) if !trimmed.is_empty() => trimmed.to_string(),
+                    _ => line,
+                };
+                (matcher.is_match(&path) && !exclude.is_excluded(&path)).then_some(path)
+            });
+
+            match output {
+                Ok(output) => return output.next(limit, cancel),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(CddError::Backend("fd: command not found".to_string()))
+    }
+}
+
+// Uses `find` over the walker roots
+pub struct FindBackend<'a> {
+    pub config: &'a WalkerConfig,
+}
+
+impl SearchBackend for FindBackend<'_> {
+    fn name(&self) -> &'static str {
+        "find"
+    }
+
+    fn search(
+        &self,
+        matcher: &Matcher,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError> {
+        let mut command = Command::new("find");
+        command
+            .args(&self.config.roots)
+            .arg("-maxdepth")
+            .arg(self.config.max_depth.to_string())
+            .args(["-type", "d"])
+            .arg(if matcher.ignore_case() {
+                "-ipath"
+            } else {
+                "-path"
+            })
+            .arg(matcher.to_glob())
+            .arg("-print");
+
+        let matcher = matcher.clone();
+        let exclude = self.config.exclude.clone();
+        CommandOutput::spawn(command, move |line| {
+            (matcher.is_match(&line) && !exclude.is_excluded(&line)).then_some(line)
+        })
+        .map_err(|e| CddError::Backend(format!("find: {e}")))?
+        .next(limit, cancel)
+    }
+}
+
+// Runs a user-supplied shell command, passing the pattern as typed as $1
+pub struct CommandBackend<'a> {
+    pub command: &'a str,
+    pub exclude: &'a Exclusions,
+}
+
+impl SearchBackend for CommandBackend<'_> {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn search(
+        &self,
+        matcher: &Matcher,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<BackendOutput, CddError> {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(self.command)
+            .arg("ccd-search")
+            .arg(matcher.pattern());
+
+        let exclude = self.exclude.clone();
+        CommandOutput::spawn(command, move |line| {
+            (!exclude.is_excluded(&line)).then_some(line)
+        })
+        .map_err(|e| CddError::Backend(format!("search command: {e}")))?
+        .next(limit, cancel)
+    }
+}
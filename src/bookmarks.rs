@@ -0,0 +1,85 @@
+// Named bookmarks, kept apart from the frequency file so they never decay or get reset
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::{data_dir, escape_field, lock_file, replace_file, unescape_field, CddError};
+
+const BOOKMARKS_FILE_NAME: &str = "bookmarks";
+const BOOKMARKS_FILE_HEADER: &str = "# ccd bookmarks v1";
+
+// Bookmark names mapped to directories, sorted by name
+pub type Bookmarks = BTreeMap<String, String>;
+
+pub struct BookmarkManager;
+
+impl BookmarkManager {
+    fn get_file_path() -> Result<PathBuf, CddError> {
+        Ok(data_dir()?.join(BOOKMARKS_FILE_NAME))
+    }
+
+    // Names are used as `@name` on the command line, so keep them to a safe set of characters
+    pub fn validate_name(name: &str) -> Result<(), CddError> {
+        let valid = !name.is_empty()
+            && !name.starts_with('-')
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if valid {
+            Ok(())
+        } else {
+            Err(CddError::Config(format!(
+                "invalid bookmark name '{name}' (use letters, digits, '-', '_' and '.')"
+            )))
+        }
+    }
+
+    pub fn load() -> Result<Bookmarks, CddError> {
+        let bookmarks_file = Self::get_file_path()?;
+        let content = match fs::read_to_string(&bookmarks_file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Bookmarks::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut bookmarks = Bookmarks::new();
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = line
+                .split_once('\t')
+                .and_then(|(name, path)| Some((unescape_field(name)?, unescape_field(path)?)));
+            match parsed {
+                Some((name, path)) => {
+                    bookmarks.insert(name, path);
+                }
+                None => eprintln!(
+                    "Warning: ignored malformed line {} in {}",
+                    index + 1,
+                    bookmarks_file.display()
+                ),
+            }
+        }
+        Ok(bookmarks)
+    }
+
+    // Load, modify and save the bookmarks while holding their lock
+    pub fn update<R>(modify: impl FnOnce(&mut Bookmarks) -> R) -> Result<R, CddError> {
+        let path = Self::get_file_path()?;
+        let _lock = lock_file(&path.with_extension("lock"))?;
+        let mut bookmarks = Self::load()?;
+        let result = modify(&mut bookmarks);
+        replace_file(&path, |file| Self::write_entries(file, &bookmarks))?;
+        Ok(result)
+    }
+
+    fn write_entries(file: &mut impl Write, bookmarks: &Bookmarks) -> io::Result<()> {
+        writeln!(file, "{BOOKMARKS_FILE_HEADER}")?;
+        for (name, path) in bookmarks {
+            writeln!(file, "{}\t{}", escape_field(name), escape_field(path))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+// Exclusion rules keeping unwanted directories out of search results and the history
+use regex::Regex;
+
+use crate::CddError;
+
+// Built-in rules, used unless exclude_defaults is turned off
+pub const DEFAULT_EXCLUDE: &[&str] = &[
+    "/proc",
+    "/sys",
+    "/dev",
+    "/run",
+    "/tmp/*",
+    "node_modules",
+    "target/",
+    ".git/objects",
+];
+
+#[derive(Debug, Clone)]
+enum Rule {
+    // Path components of a glob; `anchored` globs start at the filesystem root
+    Glob {
+        components: Vec<String>,
+        anchored: bool,
+    },
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Exclusions {
+    rules: Vec<Rule>,
+}
+
+impl Exclusions {
+    // Patterns prefixed with `re:` are regular expressions searched for anywhere in the path.
+    // Anything else is a glob over path components (see `matches_glob`).
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, CddError> {
+        let rules = patterns
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| match pattern.strip_prefix("re:") {
+                Some(regex) => Regex::new(regex).map(Rule::Regex).map_err(|e| {
+                    CddError::Config(format!("invalid exclude pattern '{pattern}': {e}"))
+                }),
+                None => Ok(Rule::Glob {
+                    components: pattern
+                        .split('/')
+                        .filter(|component| !component.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                    anchored: pattern.starts_with('/'),
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_excluded(&self, path: &str) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let path_components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        self.rules.iter().any(|rule| match rule {
+            Rule::Regex(regex) => regex.is_match(path),
+            Rule::Glob {
+                components,
+                anchored,
+            } => matches_glob(components, *anchored, &path_components),
+        })
+    }
+}
+
+// A glob matches a directory and everything below it. Globs starting with '/' are anchored at
+// the root (`/tmp/*`); others match at any depth (`node_modules`, `.git/objects`). A trailing
+// '/' is allowed but not needed. `**` matches any number of components.
+fn matches_glob(pattern: &[String], anchored: bool, path: &[&str]) -> bool {
+    if pattern.is_empty() {
+        return anchored;
+    }
+    if anchored {
+        return matches_prefix(pattern, path);
+    }
+    (0..path.len()).any(|start| matches_prefix(pattern, &path[start..]))
+}
+
+// Whether `pattern` matches the leading components of `path`
+fn matches_prefix(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => true,
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| matches_prefix(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((component, path_rest)) => {
+                let pattern: Vec<char> = first.chars().collect();
+                let text: Vec<char> = component.chars().collect();
+                match_component(&pattern, &text) && matches_prefix(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+// Match one path component against a glob with `*`, `?`, `[...]` classes (`[!...]` negates)
+// and backslash escapes
+pub fn match_component(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| match_component(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && match_component(rest, &text[1..]),
+        Some(('[', rest)) => {
+            let Some((&c, text_rest)) = text.split_first() else {
+                return false;
+            };
+            match match_class(rest, c) {
+                Some((true, after)) => match_component(after, text_rest),
+                Some((false, _)) => false,
+                // An unterminated class is a literal '['
+                None => c == '[' && match_component(rest, text_rest),
+            }
+        }
+        Some(('\\', rest)) if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && match_component(&rest[1..], &text[1..])
+        }
+        Some((&p, rest)) => text.first() == Some(&p) && match_component(rest, &text[1..]),
+    }
+}
+
+// Match `c` against the class starting after '[', returning whether it matched and the
+// pattern after the closing ']'
+fn match_class(class: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negated, mut i) = match class.first() {
+        Some('!' | '^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut matched = false;
+    let mut first = true;
+    while i < class.len() {
+        if class[i] == ']' && !first {
+            return Some((matched != negated, &class[i + 1..]));
+        }
+        first = false;
+        if i + 2 < class.len() && class[i + 1] == '-' && class[i + 2] != ']' {
+            matched |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    None
+}
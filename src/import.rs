@@ -0,0 +1,269 @@
+// Readers for the databases of other directory jumpers, used by `--import`
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::CddError;
+
+// zoxide's db.zo format version we know how to read
+const ZOXIDE_FORMAT_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportSource {
+    Zoxide,
+    Z,
+    Autojump,
+    Fasd,
+}
+
+// One directory read from another tool, with its score converted to a ccd usage count
+#[derive(Debug)]
+pub struct ImportedEntry {
+    pub path: String,
+    pub count: u32,
+    pub last_access: Option<u64>,
+}
+
+impl ImportSource {
+    pub fn parse(name: &str) -> Result<Self, CddError> {
+        match name {
+            "zoxide" => Ok(ImportSource::Zoxide),
+            "z" => Ok(ImportSource::Z),
+            "autojump" => Ok(ImportSource::Autojump),
+            "fasd" => Ok(ImportSource::Fasd),
+            other => Err(CddError::Config(format!(
+                "unknown import source '{other}' (expected zoxide, z, autojump or fasd)"
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportSource::Zoxide => "zoxide",
+            ImportSource::Z => "z",
+            ImportSource::Autojump => "autojump",
+            ImportSource::Fasd => "fasd",
+        }
+    }
+
+    // Where the tool keeps its data by default, honouring its own environment variables
+    pub fn default_path(self) -> Result<PathBuf, CddError> {
+        let home = env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .map(PathBuf::from);
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+
+        let path = match self {
+            ImportSource::Zoxide => env::var_os("_ZO_DATA_DIR")
+                .map(PathBuf::from)
+                .or_else(|| data_home.map(|dir| dir.join("zoxide")))
+                .map(|dir| dir.join("db.zo")),
+            ImportSource::Z => env::var_os("_Z_DATA")
+                .map(PathBuf::from)
+                .or_else(|| home.map(|home| home.join(".z"))),
+            ImportSource::Autojump => data_home.map(|dir| dir.join("autojump/autojump.txt")),
+            ImportSource::Fasd => env::var_os("_FASD_DATA")
+                .map(PathBuf::from)
+                .or_else(|| home.map(|home| home.join(".fasd"))),
+        };
+
+        path.ok_or_else(|| {
+            CddError::Config(format!(
+                "cannot find the {} data file: pass its path after the tool name",
+                self.name()
+            ))
+        })
+    }
+
+    pub fn read(self, path: &Path) -> Result<Vec<ImportedEntry>, CddError> {
+        match self {
+            ImportSource::Zoxide => read_zoxide(&fs::read(path)?),
+            ImportSource::Z | ImportSource::Fasd => {
+                Ok(read_pipe_separated(&fs::read_to_string(path)?))
+            }
+            ImportSource::Autojump => Ok(read_autojump(&fs::read_to_string(path)?)),
+        }
+    }
+}
+
+// zoxide ranks and z/fasd ranks grow by one per visit, so they map straight to counts
+fn rank_to_count(rank: f64) -> u32 {
+    if rank.is_finite() {
+        rank.round().clamp(1.0, u32::MAX as f64) as u32
+    } else {
+        1
+    }
+}
+
+// zoxide's db.zo: a u32 format version followed by a bincode-encoded list of directories,
+// each a length-prefixed path, an f64 rank and a u64 last access time (all little endian)
+fn read_zoxide(data: &[u8]) -> Result<Vec<ImportedEntry>, CddError> {
+    let corrupt = || CddError::Config("zoxide database is corrupt or unsupported".to_string());
+    let mut offset: usize = 0;
+    let mut take = |len: usize| -> Result<&[u8], CddError> {
+        let end = offset.checked_add(len).ok_or_else(corrupt)?;
+        let bytes = data.get(offset..end).ok_or_else(corrupt)?;
+        offset += len;
+        Ok(bytes)
+    };
+    let u64_at = |bytes: &[u8]| {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(bytes);
+        u64::from_le_bytes(buf)
+    };
+
+    let version = take(4)?;
+    let version = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+    if version != ZOXIDE_FORMAT_VERSION {
+        return Err(CddError::Config(format!(
+            "unsupported zoxide database version {version} (expected {ZOXIDE_FORMAT_VERSION})"
+        )));
+    }
+
+    let len = u64_at(take(8)?) as usize;
+    let mut entries = Vec::new();
+    for _ in 0..len {
+        let path_len = u64_at(take(8)?) as usize;
+        let path = String::from_utf8_lossy(take(path_len)?).into_owned();
+        let rank = f64::from_bits(u64_at(take(8)?));
+        let last_access = u64_at(take(8)?);
+        entries.push(ImportedEntry {
+            path,
+            count: rank_to_count(rank),
+            last_access: Some(last_access),
+        });
+    }
+    Ok(entries)
+}
+
+// z and fasd: one "path|rank|time" line per entry
+fn read_pipe_separated(content: &str) -> Vec<ImportedEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            // Paths may contain '|', so split from the right
+            let mut fields = line.rsplitn(3, '|');
+            let last_access = fields.next()?.trim().parse().ok()?;
+            let rank: f64 = fields.next()?.trim().parse().ok()?;
+            let path = fields.next()?;
+            Some(ImportedEntry {
+                path: path.to_string(),
+                count: rank_to_count(rank),
+                last_access: Some(last_access),
+            })
+        })
+        .collect()
+}
+
+// autojump: one "weight<TAB>path" line per entry. Each visit raises the weight w to
+// sqrt(w² + 100), starting at 10, so (w / 10)² approximates the number of visits.
+fn read_autojump(content: &str) -> Vec<ImportedEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (weight, path) = line.split_once('\t')?;
+            let weight: f64 = weight.trim().parse().ok()?;
+            Some(ImportedEntry {
+                path: path.to_string(),
+                count: rank_to_count((weight / 10.0).powi(2)),
+                last_access: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A db.zo as zoxide writes it
+    fn zoxide_database(version: u32, directories: &[(&str, f64, u64)]) -> Vec<u8> {
+        let mut data = version.to_le_bytes().to_vec();
+        data.extend((directories.len() as u64).to_le_bytes());
+        for (path, rank, last_access) in directories {
+            data.extend((path.len() as u64).to_le_bytes());
+            data.extend(path.as_bytes());
+            data.extend(rank.to_bits().to_le_bytes());
+            data.extend(last_access.to_le_bytes());
+        }
+        data
+    }
+
+    fn summary(entries: &[ImportedEntry]) -> Vec<(&str, u32, Option<u64>)> {
+        entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.count, entry.last_access))
+            .collect()
+    }
+
+    #[test]
+    fn zoxide_databases() {
+        let data = zoxide_database(
+            ZOXIDE_FORMAT_VERSION,
+            &[
+                ("/home/me/src", 12.4, 1_700_000_000),
+                ("/home/me/dåta", 0.2, 1_700_000_100),
+            ],
+        );
+        let entries = read_zoxide(&data).unwrap();
+        assert_eq!(
+            summary(&entries),
+            vec![
+                ("/home/me/src", 12, Some(1_700_000_000)),
+                ("/home/me/dåta", 1, Some(1_700_000_100)),
+            ]
+        );
+        assert!(read_zoxide(&zoxide_database(ZOXIDE_FORMAT_VERSION, &[]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn corrupt_zoxide_databases_are_errors() {
+        let data = zoxide_database(ZOXIDE_FORMAT_VERSION, &[("/home/me/src", 1.0, 0)]);
+        for len in [0, 3, 12, data.len() - 1] {
+            assert!(read_zoxide(&data[..len]).is_err(), "truncated to {len}");
+        }
+        assert!(read_zoxide(&zoxide_database(2, &[])).is_err());
+
+        // A path length running past the end of the file
+        let mut data = zoxide_database(ZOXIDE_FORMAT_VERSION, &[]);
+        data[4..12].copy_from_slice(&1u64.to_le_bytes());
+        data.extend(u64::MAX.to_le_bytes());
+        assert!(read_zoxide(&data).is_err());
+    }
+
+    #[test]
+    fn autojump_weights_become_visit_counts() {
+        let content = "10.0\t/home/me/once\n\
+                       31.6227766\t/home/me/ten\n\
+                       100\t/home/me/tab\there\n\
+                       not a weight\t/home/me/skipped\n\
+                       no tab\n";
+        let entries = read_autojump(content);
+        assert_eq!(
+            summary(&entries),
+            vec![
+                ("/home/me/once", 1, None),
+                ("/home/me/ten", 10, None),
+                ("/home/me/tab\there", 100, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn z_and_fasd_lines() {
+        let content = "/home/me/src|4.6|1700000000\n/home/me/a|b|2|1700000001\nbroken line\n";
+        let entries = read_pipe_separated(content);
+        assert_eq!(
+            summary(&entries),
+            vec![
+                ("/home/me/src", 5, Some(1_700_000_000)),
+                ("/home/me/a|b", 2, Some(1_700_000_001)),
+            ]
+        );
+    }
+}
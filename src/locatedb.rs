@@ -0,0 +1,432 @@
+// In-process reader for plocate and mlocate databases, so searches don't spawn `locate`
+use std::cmp::Reverse;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use memmap2::Mmap;
+
+use crate::exclude::Exclusions;
+use crate::matcher::Matcher;
+use crate::CddError;
+
+const PLOCATE_MAGIC: &[u8] = b"\0plocate";
+const MLOCATE_MAGIC: &[u8] = b"\0mlocate";
+// Largest decompressed plocate block we try before giving up
+const MAX_BLOCK_SIZE: usize = 64 << 20;
+// How long a search scans before returning the best matches found so far, leaving the rest
+// of the database to `resume`
+pub const SCAN_BUDGET: Duration = Duration::from_secs(2);
+// Entries visited between checks of the scan budget
+const BUDGET_CHECK_INTERVAL: usize = 4096;
+const DATABASE_PATHS: &[&str] = &["/var/lib/plocate/plocate.db", "/var/lib/mlocate/mlocate.db"];
+
+// mlocate entry types
+const MLOCATE_FILE: u8 = 0;
+const MLOCATE_DIRECTORY: u8 = 1;
+const MLOCATE_END: u8 = 2;
+
+// Opened once per process and kept for the rest of the session, or why it couldn't be
+static SHARED_DATABASE: OnceLock<Result<Arc<LocateDatabase>, String>> = OnceLock::new();
+
+enum Format {
+    Plocate {
+        num_docids: usize,
+        filename_index_offset: usize,
+        dictionary: Option<(usize, usize)>,
+    },
+    Mlocate {
+        directories_offset: usize,
+    },
+}
+
+pub struct LocateDatabase {
+    data: Mmap,
+    format: Format,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Debug, Default)]
+pub struct DatabaseMatches {
+    pub directories: Vec<String>,
+    pub files_filtered: usize,
+    // Set when the search stopped at the limit, to continue it with `resume`
+    pub rest: Option<Cursor>,
+}
+
+// A scored path: the score, then shorter paths first
+pub type Candidate = (i32, Reverse<usize>, String);
+
+// Where a search stopped: the next entry to scan, if any, and the candidates found so far
+// that weren't returned yet, best first
+#[derive(Debug)]
+pub struct Cursor {
+    // The plocate block or mlocate directory offset, and the entry within it
+    position: Option<(usize, usize)>,
+    candidates: Vec<Candidate>,
+}
+
+// Move the best candidates that are directories into `directories` until it holds `limit`,
+// counting the files passed over in `files_filtered`. Returns the candidates left, best first.
+pub fn take_best(
+    mut candidates: Vec<Candidate>,
+    limit: usize,
+    directories: &mut Vec<String>,
+    files_filtered: &mut usize,
+) -> Vec<Candidate> {
+    // Best score first, then shortest
+    candidates.sort_unstable_by(|a, b| b.cmp(a));
+    let mut rest = candidates.into_iter();
+    for (_, _, path) in rest.by_ref() {
+        if Path::new(&path).is_dir() {
+            directories.push(path);
+        } else {
+            *files_filtered += 1;
+        }
+        if directories.len() >= limit {
+            break;
+        }
+    }
+    rest.collect()
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64_le(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|b| {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(b);
+        u64::from_le_bytes(bytes)
+    })
+}
+
+// Read a NUL-terminated string starting at `offset`, returning it and the offset after the NUL
+fn read_cstr(data: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let rest = data.get(offset..)?;
+    let len = rest.iter().position(|&b| b == 0)?;
+    Some((&rest[..len], offset + len + 1))
+}
+
+fn corrupt(what: &str) -> CddError {
+    CddError::Backend(format!("locate database is corrupt or unsupported: {what}"))
+}
+
+impl LocateDatabase {
+    // Map the database into memory rather than reading it, so only the pages a search
+    // touches are loaded
+    pub fn open(path: &Path) -> Result<Self, CddError> {
+        let file = File::open(path)?;
+        let modified = file.metadata().and_then(|m| m.modified()).ok();
+        // SAFETY: updatedb writes a new file and renames it over the old one, so the mapped
+        // file is never modified while we hold it
+        let data = unsafe { Mmap::map(&file)? };
+
+        let format = if data.starts_with(PLOCATE_MAGIC) {
+            Self::parse_plocate_header(&data)?
+        } else if data.starts_with(MLOCATE_MAGIC) {
+            Self::parse_mlocate_header(&data)?
+        } else {
+            return Err(corrupt("unknown file format"));
+        };
+
+        Ok(Self {
+            data,
+            format,
+            modified,
+        })
+    }
+
+    // The database from `path`, or the first readable system database. Opened on first
+    // use and shared for the rest of the process, so the TUI opens it only once. Fails with
+    // the reason it can't be read in-process, e.g. because it is only readable by the locate
+    // group, as on most distributions.
+    pub fn shared(path: Option<&Path>) -> Result<Arc<LocateDatabase>, String> {
+        SHARED_DATABASE
+            .get_or_init(|| {
+                let candidates: Vec<PathBuf> = match path {
+                    Some(path) => vec![path.to_path_buf()],
+                    None => DATABASE_PATHS.iter().map(PathBuf::from).collect(),
+                };
+                let mut reason = None;
+                for candidate in &candidates {
+                    match Self::open(candidate) {
+                        Ok(database) => return Ok(Arc::new(database)),
+                        // A missing database is only worth mentioning if nothing else is wrong
+                        Err(CddError::IoError(e))
+                            if e.kind() == io::ErrorKind::NotFound && path.is_none() => {}
+                        Err(CddError::IoError(e)) => {
+                            reason.get_or_insert(format!("{}: {e}", candidate.display()));
+                        }
+                        Err(e) => {
+                            reason.get_or_insert(format!("{}: {e}", candidate.display()));
+                        }
+                    }
+                }
+                Err(reason.unwrap_or_else(|| "no plocate or mlocate database found".to_string()))
+            })
+            .clone()
+    }
+
+    pub fn age(&self) -> Option<Duration> {
+        self.modified
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+    }
+
+    // Header layout from plocate's db.h (little endian, version 1 and later)
+    fn parse_plocate_header(data: &[u8]) -> Result<Format, CddError> {
+        let version = read_u32_le(data, 8).ok_or_else(|| corrupt("short header"))?;
+        let num_docids = read_u32_le(data, 20).ok_or_else(|| corrupt("short header"))?;
+        let filename_index_offset =
+            read_u64_le(data, 32).ok_or_else(|| corrupt("short header"))? as usize;
+
+        let dictionary = if version >= 1 {
+            let length = read_u32_le(data, 44).ok_or_else(|| corrupt("short header"))? as usize;
+            let offset = read_u64_le(data, 48).ok_or_else(|| corrupt("short header"))? as usize;
+            if offset
+                .checked_add(length)
+                .is_none_or(|end| end > data.len())
+            {
+                return Err(corrupt("dictionary out of bounds"));
+            }
+            (length > 0).then_some((offset, length))
+        } else {
+            None
+        };
+
+        let index_end = filename_index_offset + (num_docids as usize + 1) * 8;
+        if index_end > data.len() {
+            return Err(corrupt("filename index out of bounds"));
+        }
+
+        Ok(Format::Plocate {
+            num_docids: num_docids as usize,
+            filename_index_offset,
+            dictionary,
+        })
+    }
+
+    // Header layout from mlocate's db.h (big endian)
+    fn parse_mlocate_header(data: &[u8]) -> Result<Format, CddError> {
+        let conf_size = data
+            .get(8..12)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| corrupt("short header"))?;
+        let (_root, after_root) = read_cstr(data, 16).ok_or_else(|| corrupt("short header"))?;
+
+        Ok(Format::Mlocate {
+            directories_offset: after_root + conf_size,
+        })
+    }
+
+    // Find the `limit` best-scoring directories matching `matcher` that aren't excluded,
+    // counting the matching files seen along the way. Every matching path is scored, not just the first ones in
+    // database order; paths are only scored once the cheaper `is_match` accepts them, and
+    // the scan stops after SCAN_BUDGET so a huge database still returns promptly, leaving
+    // the rest to `resume`. plocate doesn't record which paths are directories, so
+    // candidates are checked on disk afterwards, best first.
+    pub fn search(
+        &self,
+        matcher: &Matcher,
+        exclude: &Exclusions,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<DatabaseMatches, CddError> {
+        let start = match self.format {
+            Format::Plocate { .. } => (0, 0),
+            Format::Mlocate { directories_offset } => (directories_offset, 0),
+        };
+        let cursor = Cursor {
+            position: Some(start),
+            candidates: Vec::new(),
+        };
+        self.resume(matcher, exclude, cursor, limit, cancel)
+    }
+
+    // Continue a search where it stopped, returning the next `limit` directories
+    pub fn resume(
+        &self,
+        matcher: &Matcher,
+        exclude: &Exclusions,
+        cursor: Cursor,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<DatabaseMatches, CddError> {
+        let mut matches = DatabaseMatches::default();
+        let mut candidates = cursor.candidates;
+        let deadline = Instant::now() + SCAN_BUDGET;
+        let mut visited = 0;
+
+        // Returning false leaves the entry to the next scan
+        let mut visit = |path: &[u8], is_dir: Option<bool>| {
+            if cancel.load(Ordering::Relaxed) {
+                return false;
+            }
+            visited += 1;
+            if visited % BUDGET_CHECK_INTERVAL == 0 && Instant::now() >= deadline {
+                return false;
+            }
+            let path = String::from_utf8_lossy(path);
+            if !matcher.is_match(&path) || exclude.is_excluded(&path) {
+                return true;
+            }
+            if is_dir == Some(false) {
+                matches.files_filtered += 1;
+                return true;
+            }
+            if let Some(found) = matcher.score(&path) {
+                candidates.push((found.score, Reverse(path.len()), path.into_owned()));
+            }
+            true
+        };
+
+        let position = match (cursor.position, &self.format) {
+            (None, _) => None,
+            (
+                Some(start),
+                &Format::Plocate {
+                    num_docids,
+                    filename_index_offset,
+                    dictionary,
+                },
+            ) => self.scan_plocate(
+                num_docids,
+                filename_index_offset,
+                dictionary,
+                start,
+                &mut visit,
+            )?,
+            (Some(start), Format::Mlocate { .. }) => self.scan_mlocate(start, &mut visit)?,
+        };
+
+        let candidates = take_best(
+            candidates,
+            limit,
+            &mut matches.directories,
+            &mut matches.files_filtered,
+        );
+        if position.is_some() || !candidates.is_empty() {
+            matches.rest = Some(Cursor {
+                position,
+                candidates,
+            });
+        }
+        Ok(matches)
+    }
+
+    // Every plocate document is a zstd-compressed block of NUL-terminated paths
+    fn scan_plocate(
+        &self,
+        num_docids: usize,
+        filename_index_offset: usize,
+        dictionary: Option<(usize, usize)>,
+        (first_docid, first_entry): (usize, usize),
+        visit: &mut impl FnMut(&[u8], Option<bool>) -> bool,
+    ) -> Result<Option<(usize, usize)>, CddError> {
+        let mut decompressor = match dictionary {
+            Some((offset, length)) => {
+                zstd::bulk::Decompressor::with_dictionary(&self.data[offset..offset + length])?
+            }
+            None => zstd::bulk::Decompressor::new()?,
+        };
+        let mut block = Vec::new();
+
+        for docid in first_docid..num_docids {
+            let start = read_u64_le(&self.data, filename_index_offset + docid * 8)
+                .ok_or_else(|| corrupt("filename index"))? as usize;
+            let end = read_u64_le(&self.data, filename_index_offset + (docid + 1) * 8)
+                .ok_or_else(|| corrupt("filename index"))? as usize;
+            let compressed = self
+                .data
+                .get(start..end)
+                .ok_or_else(|| corrupt("block out of bounds"))?;
+
+            // plocate records the block size; otherwise grow the buffer until it fits
+            let mut capacity = match zstd::zstd_safe::get_frame_content_size(compressed) {
+                Ok(Some(size)) => size as usize,
+                _ => compressed.len() * 8,
+            };
+            loop {
+                block.clear();
+                block.reserve(capacity);
+                match decompressor.decompress_to_buffer(compressed, &mut block) {
+                    Ok(_) => break,
+                    Err(_) if capacity < MAX_BLOCK_SIZE => capacity *= 2,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            let skip = if docid == first_docid { first_entry } else { 0 };
+            let paths = block.split(|&b| b == 0).filter(|path| !path.is_empty());
+            for (entry, path) in paths.enumerate().skip(skip) {
+                if !visit(path, None) {
+                    return Ok(Some((docid, entry)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    // mlocate stores each directory followed by the names of its entries. Scanning starts at
+    // the directory at `first_offset`.
+    fn scan_mlocate(
+        &self,
+        (first_offset, first_entry): (usize, usize),
+        visit: &mut impl FnMut(&[u8], Option<bool>) -> bool,
+    ) -> Result<Option<(usize, usize)>, CddError> {
+        let data = &self.data;
+        let mut offset = first_offset;
+        let mut path = Vec::new();
+
+        while offset < data.len() {
+            // 16-byte directory header: modification time and padding
+            let (dir, mut next) =
+                read_cstr(data, offset + 16).ok_or_else(|| corrupt("directory header"))?;
+
+            let skip = if offset == first_offset {
+                first_entry
+            } else {
+                0
+            };
+            for entry in 0.. {
+                let entry_type = *data.get(next).ok_or_else(|| corrupt("directory entry"))?;
+                next += 1;
+                if entry_type == MLOCATE_END {
+                    break;
+                }
+                let (name, after_name) =
+                    read_cstr(data, next).ok_or_else(|| corrupt("directory entry"))?;
+                next = after_name;
+                if entry < skip {
+                    continue;
+                }
+
+                path.clear();
+                path.extend_from_slice(dir);
+                if !dir.ends_with(b"/") {
+                    path.push(b'/');
+                }
+                path.extend_from_slice(name);
+
+                let is_dir = match entry_type {
+                    MLOCATE_FILE => false,
+                    MLOCATE_DIRECTORY => true,
+                    _ => return Err(corrupt("unknown entry type")),
+                };
+                if !visit(&path, Some(is_dir)) {
+                    return Ok(Some((offset, entry)));
+                }
+            }
+
+            offset = next;
+        }
+
+        Ok(None)
+    }
+}
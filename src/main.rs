@@ -1,14 +1,41 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::error::Error;
 use std::fmt;
 use std::fs;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::{self, BufWriter, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use chrono::Local;
 
+mod backends;
+mod bookmarks;
+mod exclude;
+mod import;
+mod locatedb;
+mod matcher;
+mod shell;
+mod transfer;
+mod walker;
+mod worker;
+
+use backends::{
+    BackendKind, BackendOutput, BackendSpec, CommandBackend, Continuation, FdBackend, FindBackend,
+    FrequencyBackend, LocateBackend, SearchBackend, WalkBackend,
+};
+use bookmarks::{BookmarkManager, Bookmarks};
+use exclude::{Exclusions, DEFAULT_EXCLUDE};
+use import::ImportSource;
+use locatedb::LocateDatabase;
+use matcher::{CaseMode, Match, Matcher};
+use shell::{Hook, Shell};
+use transfer::{ExportFormat, MergeStrategy, RewriteRule};
+use walker::WalkerConfig;
+use worker::SearchWorker;
+
 // Include the compiled shell function
 include!(concat!(env!("OUT_DIR"), "/ccd_shell_function.rs"));
 
@@ -29,15 +56,56 @@ use ratatui::{
 };
 
 // Constants
-const LOCATE_LIMIT: &str = "100";
+const DEFAULT_RESULT_LIMIT: usize = 100;
 const PAGE_SIZE: usize = 10;
-const FREQUENCY_FILE_NAME: &str = ".ccd_frequency";
+// How often the TUI redraws while waiting for input or search results
+const TICK_RATE: Duration = Duration::from_millis(50);
+const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const FREQUENCY_FILE_NAME: &str = "frequency";
+// Where versions before XDG support kept the frequency file, relative to HOME
+const LEGACY_FREQUENCY_FILE_NAME: &str = ".ccd_frequency";
+const CONFIG_FILE_NAME: &str = "config";
+const CONFIG_KEYS: &[&str] = &[
+    "backends",
+    "result_limit",
+    "walk_roots",
+    "walk_depth",
+    "walk_ignore",
+    "locate_max_age_days",
+    "locate_database",
+    "search_command",
+    "auto_clean",
+    "clean_grace_days",
+    "track_weight",
+    "exclude",
+    "exclude_defaults",
+    "case",
+];
+const FREQUENCY_FILE_VERSION: u32 = 2;
+const FREQUENCY_FILE_HEADER: &str = "# ccd frequency v";
+const DEFAULT_LOCATE_MAX_AGE_DAYS: u64 = 7;
+// Counts are aged once their total exceeds this, so old favourites fade over time
+const MAX_TOTAL_COUNT: u64 = 10_000;
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+// Directories missing for less than this are kept, in case their disk is just unmounted
+const DEFAULT_CLEAN_GRACE_DAYS: u64 = 30;
+// Visits recorded by the shell hook count this much, TUI selections count 1
+const DEFAULT_TRACK_WEIGHT: u32 = 1;
+// The shell hook doesn't count a directory again if it was recorded this recently
+const TRACK_DEDUPE_SECS: u64 = 10;
+// Metadata field recording when a directory was first found missing
+const MISSING_SINCE_KEY: &str = "missing_since";
+// How much frecency counts against the fuzzy match score when ranking search results
+const FRECENCY_WEIGHT: f64 = 20.0;
 
 // View modes for the interactive interface
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ViewMode {
     Search,
     Frequent,
+    Bookmarks,
 }
 
 // Custom error types
@@ -46,6 +114,12 @@ enum CddError {
     LocateCommand(String),
     NoDirectoriesFound,
     DirectoryNotFound(String),
+    FrequencyFile(String),
+    Config(String),
+    Backend(String),
+    Import(String),
+    InvalidPattern(String),
+    Cancelled,
     IoError(io::Error),
 }
 
@@ -55,6 +129,12 @@ impl fmt::Display for CddError {
             CddError::LocateCommand(msg) => write!(f, "Locate command error: {msg}"),
             CddError::NoDirectoriesFound => write!(f, "No directories found"),
             CddError::DirectoryNotFound(path) => write!(f, "Directory not found: {path}"),
+            CddError::FrequencyFile(msg) => write!(f, "Frequency file error: {msg}"),
+            CddError::Config(msg) => write!(f, "Configuration error: {msg}"),
+            CddError::Backend(msg) => write!(f, "Search backend error: {msg}"),
+            CddError::Import(msg) => write!(f, "Import error: {msg}"),
+            CddError::InvalidPattern(msg) => write!(f, "Invalid pattern: {msg}"),
+            CddError::Cancelled => write!(f, "Search cancelled"),
             CddError::IoError(err) => write!(f, "IO error: {err}"),
         }
     }
@@ -68,16 +148,317 @@ impl From<io::Error> for CddError {
     }
 }
 
+// XDG base directories, each with a CCD_* override. Never falls back to a shared
+// location like /tmp when HOME is unset.
+fn base_dir(override_var: &str, xdg_var: &str, home_fallback: &str) -> Result<PathBuf, CddError> {
+    if let Some(dir) = env::var_os(override_var).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    // The XDG spec says relative paths are invalid and should be ignored
+    if let Some(dir) = env::var_os(xdg_var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok(dir.join("ccd"));
+    }
+    match env::var_os("HOME").filter(|home| !home.is_empty()) {
+        Some(home) => Ok(Path::new(&home).join(home_fallback).join("ccd")),
+        None => Err(CddError::Config(format!(
+            "cannot find a place for ccd's files: set HOME, {xdg_var} or {override_var}"
+        ))),
+    }
+}
+
+fn data_dir() -> Result<PathBuf, CddError> {
+    base_dir("CCD_DATA_DIR", "XDG_DATA_HOME", ".local/share")
+}
+
+fn config_dir() -> Result<PathBuf, CddError> {
+    base_dir("CCD_CONFIG_DIR", "XDG_CONFIG_HOME", ".config")
+}
+
+// Settings from the config file, each overridden by the matching CCD_* environment
+// variable (e.g. `result_limit` by CCD_RESULT_LIMIT)
+struct Settings {
+    file: HashMap<String, String>,
+}
+
+impl Settings {
+    fn load() -> Result<Self, CddError> {
+        let path = config_dir()?.join(CONFIG_FILE_NAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        // Lines are "key = value"; blank lines and lines starting with '#' are ignored
+        let mut file = HashMap::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                CddError::Config(format!(
+                    "{}:{}: expected 'key = value'",
+                    path.display(),
+                    index + 1
+                ))
+            })?;
+            let key = key.trim();
+            if !CONFIG_KEYS.contains(&key) {
+                return Err(CddError::Config(format!(
+                    "{}:{}: unknown setting '{key}'",
+                    path.display(),
+                    index + 1
+                )));
+            }
+            file.insert(key.to_string(), value.trim().to_string());
+        }
+
+        Ok(Self { file })
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        env::var(format!("CCD_{}", key.to_uppercase()))
+            .ok()
+            .or_else(|| self.file.get(key).cloned())
+    }
+
+    // The built-in exclusion rules unless turned off, plus the whitespace-separated `exclude`
+    // list (patterns may contain ':' and ',', e.g. regexes)
+    fn exclusions(&self) -> Result<Exclusions, CddError> {
+        let mut patterns = Vec::new();
+        let defaults = match self.get("exclude_defaults") {
+            Some(value) => parse_bool("exclude_defaults", &value)?,
+            None => true,
+        };
+        if defaults {
+            patterns.extend(DEFAULT_EXCLUDE.iter().map(|pattern| pattern.to_string()));
+        }
+        if let Some(value) = self.get("exclude") {
+            patterns.extend(value.split_whitespace().map(str::to_string));
+        }
+        Exclusions::new(&patterns)
+    }
+}
+
+// Search settings, read from the config file and CCD_* environment variables
+#[derive(Debug, Clone)]
+struct SearchConfig {
+    backends: Vec<BackendSpec>,
+    // Number of directories each backend is asked for, and how many more to load on scroll
+    result_limit: usize,
+    walker: WalkerConfig,
+    locate_max_age: Duration,
+    locate_database: Option<PathBuf>,
+    search_command: Option<String>,
+    // Directories never shown in results
+    exclude: Exclusions,
+    case: CaseMode,
+}
+
+impl SearchConfig {
+    fn load() -> Result<Self, CddError> {
+        let settings = Settings::load()?;
+        let home = env::var("HOME").ok();
+        let exclude = settings.exclusions()?;
+        let mut config = Self {
+            backends: BackendSpec::default_list(),
+            result_limit: DEFAULT_RESULT_LIMIT,
+            walker: WalkerConfig::new(home.as_deref(), exclude.clone()),
+            locate_max_age: Duration::from_secs(DEFAULT_LOCATE_MAX_AGE_DAYS * 24 * 60 * 60),
+            locate_database: settings.get("locate_database").map(PathBuf::from),
+            search_command: settings.get("search_command"),
+            exclude,
+            case: CaseMode::Smart,
+        };
+
+        if let Some(value) = settings.get("backends") {
+            config.backends = BackendSpec::parse_list(&value)?;
+        } else if let Ok(value) = env::var("CCD_BACKEND") {
+            config.backends = BackendSpec::parse_legacy(&value)?;
+        }
+        if let Some(value) = settings.get("result_limit") {
+            config.result_limit = parse_number("result_limit", &value)?;
+            if config.result_limit == 0 {
+                return Err(CddError::Config(
+                    "result_limit must be at least 1".to_string(),
+                ));
+            }
+        }
+        if let Some(value) = settings.get("walk_roots") {
+            config.walker.roots = env::split_paths(&value).collect();
+        }
+        if let Some(value) = settings.get("case") {
+            config.case = CaseMode::parse(&value)?;
+        }
+        if let Some(value) = settings.get("walk_depth") {
+            config.walker.max_depth = parse_number("walk_depth", &value)?;
+        }
+        if let Some(value) = settings.get("walk_ignore") {
+            config.walker.ignore = value
+                .split(':')
+                .filter(|rule| !rule.is_empty())
+                .map(|rule| rule.to_string())
+                .collect();
+        }
+        if let Some(value) = settings.get("locate_max_age_days") {
+            let days: u64 = parse_number("locate_max_age_days", &value)?;
+            config.locate_max_age = Duration::from_secs(days * 24 * 60 * 60);
+        }
+
+        let wants_command = config
+            .backends
+            .iter()
+            .any(|spec| spec.kind == BackendKind::Command);
+        if wants_command && config.search_command.is_none() {
+            return Err(CddError::Config(
+                "the 'command' backend requires search_command (CCD_SEARCH_COMMAND) to be set"
+                    .to_string(),
+            ));
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, CddError> {
+    value
+        .parse()
+        .map_err(|_| CddError::Config(format!("{name} must be a number, got '{value}'")))
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, CddError> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(CddError::Config(format!(
+            "{name} must be true or false, got '{value}'"
+        ))),
+    }
+}
+
+// Settings for removing directories that no longer exist from the frequency file
+#[derive(Debug, Clone, Copy)]
+struct CleanConfig {
+    // Clean up every time the frequency file is saved
+    auto: bool,
+    grace: Duration,
+}
+
+impl CleanConfig {
+    fn load() -> Result<Self, CddError> {
+        let settings = Settings::load()?;
+        let mut config = Self {
+            auto: false,
+            grace: Duration::from_secs(DEFAULT_CLEAN_GRACE_DAYS * DAY),
+        };
+        if let Some(value) = settings.get("auto_clean") {
+            config.auto = parse_bool("auto_clean", &value)?;
+        }
+        if let Some(value) = settings.get("clean_grace_days") {
+            let days: u64 = parse_number("clean_grace_days", &value)?;
+            config.grace = Duration::from_secs(days * DAY);
+        }
+        Ok(config)
+    }
+}
+
+// Directories dropped, or only marked as missing, by FrequencyManager::clean
+#[derive(Debug, Default)]
+struct CleanReport {
+    removed: Vec<String>,
+    // Missing, but still within the grace period, with the time they were first found missing
+    pending: Vec<(String, u64)>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Count weighted by how recently the directory was used, like z and zoxide
+fn frecency(count: u32, last_access: u64, now: u64) -> f64 {
+    let age = now.saturating_sub(last_access);
+    let weight = if age < HOUR {
+        4.0
+    } else if age < DAY {
+        2.0
+    } else if age < WEEK {
+        0.5
+    } else {
+        0.25
+    };
+    count as f64 * weight
+}
+
 // Data structures
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct FrequencyEntry {
+    count: u32,
+    // Seconds since the Unix epoch, 0 if unknown
+    last_access: u64,
+    // Free-form per-entry fields (tags, aliases, notes, ...), kept across load and save
+    metadata: BTreeMap<String, String>,
+}
+
+impl FrequencyEntry {
+    fn new(count: u32, last_access: u64) -> Self {
+        Self {
+            count,
+            last_access,
+            metadata: BTreeMap::new(),
+        }
+    }
+}
+
+type FrequencyMap = HashMap<String, FrequencyEntry>;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct DirectoryEntry {
     path: String,
     count: u32,
+    last_access: u64,
+    // Names of the backends that produced this entry, in configured order
+    sources: Vec<&'static str>,
+    // Set in the bookmarks view
+    bookmark: Option<String>,
+    // Fuzzy match score, and the matched character indices of `path` to highlight
+    score: i32,
+    positions: Vec<usize>,
 }
 
 impl DirectoryEntry {
-    fn new(path: String, count: u32) -> Self {
-        Self { path, count }
+    fn new(path: String, frequency: &FrequencyEntry, sources: Vec<&'static str>) -> Self {
+        Self {
+            path,
+            count: frequency.count,
+            last_access: frequency.last_access,
+            sources,
+            bookmark: None,
+            score: 0,
+            positions: Vec::new(),
+        }
+    }
+
+    fn with_match(mut self, found: Match) -> Self {
+        self.score = found.score;
+        self.positions = found.positions;
+        self
+    }
+
+    fn frecency(&self, now: u64) -> f64 {
+        frecency(self.count, self.last_access, now)
+    }
+
+    // The match score plus a bonus growing with the logarithm of frecency, so a frequently
+    // used directory wins unless another one matches clearly better
+    fn rank(&self, now: u64) -> f64 {
+        self.score as f64 + FRECENCY_WEIGHT * self.frecency(now).ln_1p()
     }
 }
 
@@ -85,13 +466,16 @@ impl DirectoryEntry {
 struct SearchResult {
     directories: Vec<DirectoryEntry>,
     files_filtered: usize,
+    // True when a backend stopped at the limit, so loading more may find more
+    more_available: bool,
 }
 
 impl SearchResult {
-    fn new(directories: Vec<DirectoryEntry>, files_filtered: usize) -> Self {
+    fn new(directories: Vec<DirectoryEntry>, files_filtered: usize, more_available: bool) -> Self {
         Self {
             directories,
             files_filtered,
+            more_available,
         }
     }
 }
@@ -102,14 +486,29 @@ struct App {
     list_state: ListState,
     should_quit: bool,
     user_selected: bool,
-    frequency_map: HashMap<String, u32>,
+    frequency_map: FrequencyMap,
+    bookmarks: Bookmarks,
     view_mode: ViewMode,
     files_filtered: usize,
+    more_available: bool,
+    config: SearchConfig,
+    worker: SearchWorker,
+    searching: bool,
+    reset_selection: bool,
+    spinner_frame: usize,
+    // Why the input can't be searched for, e.g. an invalid regular expression
+    pattern_error: Option<String>,
+    // Why the last search failed, other than finding nothing
+    search_error: Option<String>,
+    case_mode: CaseMode,
+    // Shown below the results, e.g. why searches are slower than they could be
+    notice: Option<String>,
 }
 
 impl App {
-    fn new() -> Result<Self, CddError> {
+    fn new(config: SearchConfig) -> Result<Self, CddError> {
         let frequency_map = FrequencyManager::load()?;
+        let bookmarks = BookmarkManager::load()?;
         Ok(Self {
             input: String::new(),
             directories: Vec::new(),
@@ -117,41 +516,117 @@ impl App {
             should_quit: false,
             user_selected: false,
             frequency_map,
+            bookmarks,
             view_mode: ViewMode::Search,
             files_filtered: 0,
+            more_available: false,
+            worker: SearchWorker::spawn(config.clone()),
+            case_mode: config.case,
+            notice: locate_database_notice(&config),
+            config,
+            searching: false,
+            reset_selection: false,
+            spinner_frame: 0,
+            pattern_error: None,
+            search_error: None,
         })
     }
 
-    fn search_directories(&mut self) -> Result<(), CddError> {
-        if self.input.is_empty() {
-            self.directories.clear();
-            self.files_filtered = 0;
-            self.list_state.select(None);
-            return Ok(());
-        }
+    fn search_directories(&mut self) {
+        self.more_available = false;
+        self.search_error = None;
 
-        // Search and handle the case where no results are found
-        match DirectorySearcher::search(&self.input, &self.frequency_map) {
-            Ok(search_result) => {
-                self.directories = search_result.directories;
-                self.files_filtered = search_result.files_filtered;
-            }
-            Err(CddError::NoDirectoriesFound) => {
-                // Clear results when no directories are found
+        let matcher = match self.matcher() {
+            Some(matcher) if !self.input.is_empty() => matcher,
+            _ => {
+                self.worker.cancel();
+                self.searching = false;
                 self.directories.clear();
                 self.files_filtered = 0;
+                self.list_state.select(None);
+                return;
+            }
+        };
+
+        // Results arrive through apply_search_updates; the first batch resets the selection
+        self.worker
+            .submit(matcher, self.config.result_limit, &self.frequency_map);
+        self.searching = true;
+        self.reset_selection = true;
+    }
+
+    // The matcher for the input, or None after recording why the input is invalid
+    fn matcher(&mut self) -> Option<Matcher> {
+        match Matcher::new(&self.input, self.case_mode) {
+            Ok(matcher) => {
+                self.pattern_error = None;
+                Some(matcher)
+            }
+            Err(e) => {
+                self.pattern_error = Some(e.to_string());
+                self.directories.clear();
+                self.list_state.select(None);
+                None
             }
-            Err(e) => return Err(e),
         }
+    }
 
-        // Reset selection to first item if we have results
-        if !self.directories.is_empty() {
-            self.list_state.select(Some(0));
-        } else {
-            self.list_state.select(None);
+    // Continue the search once the user scrolls to the end of the list
+    fn load_more_directories(&mut self) {
+        if self.view_mode != ViewMode::Search || !self.more_available || self.searching {
+            return;
         }
 
-        Ok(())
+        self.worker
+            .load_more(self.config.result_limit, &self.frequency_map);
+        self.searching = true;
+    }
+
+    // Take any results the background search has produced since the last frame
+    fn apply_search_updates(&mut self) {
+        for update in self.worker.poll() {
+            if update.done {
+                self.searching = false;
+            }
+
+            let selected = self.get_selected_directory().cloned();
+            match update.result {
+                Ok(search_result) => {
+                    self.search_error = None;
+                    self.directories = search_result.directories;
+                    self.files_filtered = search_result.files_filtered;
+                    self.more_available = search_result.more_available;
+                }
+                Err(e) => {
+                    // Clear results when no directories are found or the search failed,
+                    // saying why in the latter case
+                    self.search_error = match e {
+                        CddError::NoDirectoriesFound => None,
+                        e => Some(e.to_string()),
+                    };
+                    self.directories.clear();
+                    self.files_filtered = 0;
+                    self.more_available = false;
+                }
+            }
+
+            // Select the first result of a new query; otherwise keep the selection on the
+            // same directory, wherever it sorts now
+            let index = if std::mem::take(&mut self.reset_selection) {
+                None
+            } else {
+                selected.and_then(|path| self.directories.iter().position(|e| e.path == path))
+            };
+            if self.directories.is_empty() {
+                self.list_state.select(None);
+            } else {
+                self.list_state.select(Some(index.unwrap_or(0)));
+            }
+        }
+
+        if self.searching {
+            self.spinner_frame = self.spinner_frame.wrapping_add(1);
+        }
     }
 
     fn navigate(&mut self, direction: NavigationDirection) {
@@ -169,6 +644,10 @@ impl App {
         };
 
         self.list_state.select(Some(new_index));
+
+        if new_index == self.directories.len() - 1 {
+            self.load_more_directories();
+        }
     }
 
     fn calculate_next_index(&self) -> usize {
@@ -213,9 +692,11 @@ impl App {
             let path = selected_dir.clone();
             let selected_index = self.list_state.selected().unwrap_or(0);
 
-            // Remove from frequency map and save
-            self.frequency_map.remove(&path);
-            FrequencyManager::save(&self.frequency_map)?;
+            // Remove from the frequency file, picking up changes other ccd processes made
+            self.frequency_map = FrequencyManager::update(|frequency_map| {
+                frequency_map.remove(&path);
+                frequency_map.clone()
+            })?;
 
             match self.view_mode {
                 ViewMode::Frequent => {
@@ -233,10 +714,18 @@ impl App {
                         self.list_state.select(Some(selected_index));
                     }
                 }
+                ViewMode::Bookmarks => {
+                    // Bookmarks stay, only their count is reset
+                    if let Some(entry) = self.directories.get_mut(selected_index) {
+                        entry.count = 0;
+                        entry.last_access = 0;
+                    }
+                }
                 ViewMode::Search => {
                     // In search mode, update the entry to show count as 0
                     if let Some(entry) = self.directories.get_mut(selected_index) {
                         entry.count = 0;
+                        entry.last_access = 0;
                     }
 
                     // Re-sort the directories since frequency changed
@@ -261,17 +750,25 @@ impl App {
             .map(|entry| &entry.path)
     }
 
+    // Cycle through the search, frequent and bookmarks views
     fn toggle_view_mode(&mut self) {
         match self.view_mode {
             ViewMode::Search => {
                 self.view_mode = ViewMode::Frequent;
+                // Results of a search still running would overwrite the frequent list
+                self.worker.cancel();
+                self.searching = false;
                 self.show_frequent_directories();
             }
             ViewMode::Frequent => {
+                self.view_mode = ViewMode::Bookmarks;
+                self.show_bookmarks();
+            }
+            ViewMode::Bookmarks => {
                 self.view_mode = ViewMode::Search;
                 // Return to search mode - if there's input, search, otherwise clear
                 if !self.input.is_empty() {
-                    let _ = self.search_directories();
+                    self.search_directories();
                 } else {
                     self.directories.clear();
                     self.list_state.select(None);
@@ -281,26 +778,25 @@ impl App {
     }
 
     fn show_frequent_directories(&mut self) {
-        // Get all directories with frequency > 0, sorted by frequency
+        // Get all directories with frequency > 0, sorted by match and frecency
+        // Filter on the input first, so only matching directories are checked on disk
+        let Some(matcher) = self.matcher() else {
+            return;
+        };
         let mut frequent_dirs: Vec<DirectoryEntry> = self
             .frequency_map
             .iter()
-            .filter(|(path, count)| **count > 0 && Path::new(path).is_dir())
-            .map(|(path, count)| DirectoryEntry::new(path.clone(), *count))
+            .filter(|(_, entry)| entry.count > 0)
+            .filter_map(|(path, entry)| Some((path, entry, matcher.score(path)?)))
+            .filter(|(path, _, _)| {
+                !self.config.exclude.is_excluded(path) && Path::new(path).is_dir()
+            })
+            .map(|(path, entry, found)| {
+                DirectoryEntry::new(path.clone(), entry, vec!["frequency"]).with_match(found)
+            })
             .collect();
 
-        // Sort by frequency (descending), then by path length (ascending)
-        frequent_dirs.sort_by(|a, b| b.count.cmp(&a.count).then(a.path.len().cmp(&b.path.len())));
-
-        // Apply search filter if there's input
-        if !self.input.is_empty() {
-            frequent_dirs.retain(|entry| {
-                entry
-                    .path
-                    .to_lowercase()
-                    .contains(&self.input.to_lowercase())
-            });
-        }
+        DirectorySearcher::sort_directories(&mut frequent_dirs);
 
         self.directories = frequent_dirs;
 
@@ -312,31 +808,63 @@ impl App {
         }
     }
 
-    fn handle_character_input(&mut self, c: char) {
-        self.input.push(c);
+    fn show_bookmarks(&mut self) {
+        // Match the input against both names and paths, keeping the list sorted by name
+        let Some(matcher) = self.matcher() else {
+            return;
+        };
+        self.directories = self
+            .bookmarks
+            .iter()
+            .filter_map(|(name, path)| {
+                // Only path matches are highlighted
+                let found = match matcher.score(path) {
+                    Some(found) => found,
+                    None => matcher.score(name).map(|_| Match::default())?,
+                };
+                let frequency = self.frequency_map.get(path).cloned().unwrap_or_default();
+                let mut entry = DirectoryEntry::new(path.clone(), &frequency, vec!["bookmark"])
+                    .with_match(found);
+                entry.bookmark = Some(name.clone());
+                Some(entry)
+            })
+            .collect();
 
-        // Apply search to current view mode
-        match self.view_mode {
-            ViewMode::Search => {
-                let _ = self.search_directories();
-            }
-            ViewMode::Frequent => {
-                self.show_frequent_directories();
-            }
+        if !self.directories.is_empty() {
+            self.list_state.select(Some(0));
+        } else {
+            self.list_state.select(None);
         }
     }
 
+    fn handle_character_input(&mut self, c: char) {
+        self.input.push(c);
+        self.refresh();
+    }
+
     fn handle_backspace(&mut self) {
         self.input.pop();
+        self.refresh();
+    }
+
+    // Switch to the next case mode and match the input again
+    fn toggle_case_mode(&mut self) {
+        self.case_mode = self.case_mode.next();
+        self.refresh();
+    }
 
-        // Apply search to current view mode
+    // Apply the input to the current view mode
+    fn refresh(&mut self) {
         match self.view_mode {
             ViewMode::Search => {
-                let _ = self.search_directories();
+                self.search_directories();
             }
             ViewMode::Frequent => {
                 self.show_frequent_directories();
             }
+            ViewMode::Bookmarks => {
+                self.show_bookmarks();
+            }
         }
     }
 }
@@ -356,209 +884,722 @@ enum NavigationDirection {
 struct FrequencyManager;
 
 impl FrequencyManager {
-    fn get_file_path() -> PathBuf {
-        let home = env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        Path::new(&home).join(FREQUENCY_FILE_NAME)
+    fn get_file_path() -> Result<PathBuf, CddError> {
+        let freq_file = data_dir()?.join(FREQUENCY_FILE_NAME);
+        if !freq_file.exists() {
+            Self::migrate_legacy_file(&freq_file)?;
+        }
+        Ok(freq_file)
     }
 
-    fn load() -> Result<HashMap<String, u32>, CddError> {
-        let mut frequency_map = HashMap::new();
-        let freq_file = Self::get_file_path();
-
-        if let Ok(file) = fs::File::open(&freq_file) {
-            let reader = BufReader::new(file);
-            for line in reader.lines() {
-                let line = line?;
-                if let Some((count_str, path)) = line.split_once('\t') {
-                    if let Ok(count) = count_str.parse::<u32>() {
-                        frequency_map.insert(path.to_string(), count);
-                    }
-                }
-            }
+    // Move ~/.ccd_frequency from older versions to the data directory, once
+    fn migrate_legacy_file(freq_file: &Path) -> Result<(), CddError> {
+        let Some(home) = env::var_os("HOME").filter(|home| !home.is_empty()) else {
+            return Ok(());
+        };
+        let legacy_file = Path::new(&home).join(LEGACY_FREQUENCY_FILE_NAME);
+        if !legacy_file.is_file() {
+            return Ok(());
         }
 
-        Ok(frequency_map)
+        if let Some(parent) = freq_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Fall back to copying when the data directory is on another filesystem
+        if fs::rename(&legacy_file, freq_file).is_err() {
+            fs::copy(&legacy_file, freq_file)?;
+            fs::remove_file(&legacy_file)?;
+        }
+        eprintln!("Moved {} to {}", legacy_file.display(), freq_file.display());
+        Ok(())
     }
 
-    fn save(frequency_map: &HashMap<String, u32>) -> Result<(), CddError> {
-        let freq_file = Self::get_file_path();
-        let mut file = fs::File::create(&freq_file)?;
+    // The file starts with a "# ccd frequency v2" header, followed by one line per entry:
+    // count, last access time, path and any "key=value" metadata, separated by tabs.
+    // Older files without a header are migrated on first load.
+    fn load() -> Result<FrequencyMap, CddError> {
+        // Reading still works where the lock file can't be created, e.g. a read-only data
+        // directory, but then an older file is only converted in memory
+        let lock = Self::lock().ok();
+        Self::read(lock.is_some())
+    }
 
-        for (path, count) in frequency_map {
-            writeln!(file, "{count}\t{path}")?;
+    // Load, modify and save the file while holding the lock, so concurrent ccd processes
+    // apply their changes on top of each other instead of overwriting them
+    fn update<R>(modify: impl FnOnce(&mut FrequencyMap) -> R) -> Result<R, CddError> {
+        let clean_config = CleanConfig::load()?;
+        let _lock = Self::lock()?;
+        let mut frequency_map = Self::read(true)?;
+        let result = modify(&mut frequency_map);
+        if clean_config.auto {
+            Self::clean(&mut frequency_map, clean_config.grace, unix_now());
         }
-
-        Ok(())
+        Self::save_locked(&frequency_map)?;
+        Ok(result)
     }
 
-    fn increment(path: &str) -> Result<(), CddError> {
-        let mut frequency_map = Self::load()?;
-        let count = frequency_map.get(path).unwrap_or(&0) + 1;
-        frequency_map.insert(path.to_string(), count);
-        Self::save(&frequency_map)
+    // Take the advisory lock that serializes access to the frequency file. It is released
+    // when the returned file is dropped.
+    fn lock() -> Result<fs::File, CddError> {
+        lock_file(&Self::get_file_path()?.with_extension("lock"))
     }
-}
 
-// Utility function to check if locate command is available
-fn check_locate_available() -> bool {
-    Command::new("locate").arg("--version").output().is_ok()
-}
+    // Read the frequency file, converting an older format on disk if `locked`, i.e. the caller
+    // holds the lock
+    fn read(locked: bool) -> Result<FrequencyMap, CddError> {
+        let freq_file = Self::get_file_path()?;
+        let content = match fs::read_to_string(&freq_file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let (frequency_map, version, malformed) = Self::parse_file(&freq_file, &content)?;
+
+        // Keep a copy of anything we could not read, since the next save drops it
+        if !malformed.is_empty() {
+            let backup = freq_file.with_extension("bak");
+            fs::copy(&freq_file, &backup)?;
+            eprintln!(
+                "Warning: ignored malformed lines {:?} in {}; original saved to {}",
+                malformed,
+                freq_file.display(),
+                backup.display()
+            );
+        }
 
-// Print locate unavailable message with specified severity level
-fn print_locate_unavailable_message(severity: &str) {
-    eprintln!("{severity}: The 'locate' command is not available on this system.");
-    eprintln!("Search functionality will not work without it.");
-    eprintln!("Please install the 'plocate' package and run 'sudo updatedb'.");
-    eprintln!("Example: sudo apt install plocate && sudo updatedb");
-}
+        if version < FREQUENCY_FILE_VERSION && locked {
+            let backup = freq_file.with_extension(format!("v{version}.bak"));
+            fs::copy(&freq_file, &backup)?;
+            Self::save_locked(&frequency_map)?;
+        }
 
-// Directory search module
-struct DirectorySearcher;
+        Ok(frequency_map)
+    }
 
-impl DirectorySearcher {
-    fn search(
-        pattern: &str,
-        frequency_map: &HashMap<String, u32>,
-    ) -> Result<SearchResult, CddError> {
-        // Use a HashSet to deduplicate paths from both sources
-        let mut unique_paths = std::collections::HashSet::new();
-        let mut files_filtered = 0;
-
-        // First, search using locate
-        let output = Command::new("locate")
-            .arg("--limit")
-            .arg(LOCATE_LIMIT)
-            .arg(pattern)
-            .output()
-            .map_err(|e| {
-                // Check if this is a "command not found" error
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    print_locate_unavailable_message("ERROR");
-                    std::process::exit(1);
-                }
-                CddError::LocateCommand(format!("Failed to execute locate: {e}"))
-            })?;
+    // Read a frequency file other than our own, e.g. one copied from another machine
+    fn read_file(path: &Path) -> Result<FrequencyMap, CddError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| CddError::FrequencyFile(format!("{}: {e}", path.display())))?;
+        let (frequency_map, _, malformed) = Self::parse_file(path, &content)?;
+        if !malformed.is_empty() {
+            eprintln!(
+                "Warning: ignored malformed lines {:?} in {}",
+                malformed,
+                path.display()
+            );
+        }
+        Ok(frequency_map)
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let locate_paths: Vec<&str> = stdout.lines().collect();
+    // Parse a frequency file of any supported version, returning the entries, the file's
+    // version and the numbers of lines that could not be read
+    fn parse_file(path: &Path, content: &str) -> Result<(FrequencyMap, u32, Vec<usize>), CddError> {
+        let mut lines = content.lines().enumerate().peekable();
+        let version = match lines
+            .peek()
+            .and_then(|(_, line)| line.strip_prefix(FREQUENCY_FILE_HEADER))
+        {
+            Some(version) => {
+                lines.next();
+                version.trim().parse::<u32>().map_err(|_| {
+                    CddError::FrequencyFile(format!("{}: invalid header", path.display()))
+                })?
+            }
+            None => 1,
+        };
+        if version > FREQUENCY_FILE_VERSION {
+            return Err(CddError::FrequencyFile(format!(
+                "{} was written by a newer version of ccd-pick (format v{version})",
+                path.display()
+            )));
+        }
 
-        // Count files and add directories to our set
-        for path in locate_paths {
-            if Path::new(path).is_dir() {
-                unique_paths.insert(path.to_string());
+        let mut frequency_map = HashMap::new();
+        let mut malformed = Vec::new();
+        for (index, line) in lines {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = if version == 1 {
+                Self::parse_legacy_line(line)
             } else {
-                files_filtered += 1;
+                Self::parse_line(line)
+            };
+            match parsed {
+                Some((path, entry)) => {
+                    frequency_map.insert(path, entry);
+                }
+                None => malformed.push(index + 1),
             }
         }
 
-        // Second, search through frequency map for paths that match the pattern
-        let pattern_lower = pattern.to_lowercase();
-        for path in frequency_map.keys() {
-            if path.to_lowercase().contains(&pattern_lower) && Path::new(path).is_dir() {
-                unique_paths.insert(path.clone());
-            }
+        Ok((frequency_map, version, malformed))
+    }
+
+    fn parse_line(line: &str) -> Option<(String, FrequencyEntry)> {
+        let mut fields = line.split('\t');
+        let count = fields.next()?.parse().ok()?;
+        let last_access = fields.next()?.parse().ok()?;
+        let path = unescape_field(fields.next()?)?;
+        if path.is_empty() {
+            return None;
         }
 
-        // If no directories found from either source, return error
-        if unique_paths.is_empty() {
-            return Err(CddError::NoDirectoriesFound);
+        let mut entry = FrequencyEntry::new(count, last_access);
+        for field in fields {
+            let (key, value) = split_metadata_field(field)?;
+            entry
+                .metadata
+                .insert(unescape_field(key)?, unescape_field(value)?);
         }
+        Some((path, entry))
+    }
 
-        // Convert to DirectoryEntry with frequency data
-        let mut directories: Vec<DirectoryEntry> = unique_paths
-            .into_iter()
-            .map(|path| {
-                let count = frequency_map.get(&path).unwrap_or(&0);
-                DirectoryEntry::new(path, *count)
-            })
-            .collect();
+    // Files without a header have "count\tpath" or "count\tlast_access\tpath" lines
+    fn parse_legacy_line(line: &str) -> Option<(String, FrequencyEntry)> {
+        let (count_str, rest) = line.split_once('\t')?;
+        let count = count_str.parse().ok()?;
+        let (last_access, path) = match rest.split_once('\t') {
+            Some((time_str, path)) => match time_str.parse::<u64>() {
+                Ok(last_access) => (last_access, path),
+                Err(_) => (0, rest),
+            },
+            None => (0, rest),
+        };
+        if path.is_empty() {
+            return None;
+        }
+        Some((path.to_string(), FrequencyEntry::new(count, last_access)))
+    }
 
-        Self::sort_directories(&mut directories);
-        Ok(SearchResult::new(directories, files_filtered))
+    // Must be called with the lock held
+    fn save_locked(frequency_map: &FrequencyMap) -> Result<(), CddError> {
+        replace_file(&Self::get_file_path()?, |file| {
+            Self::write_entries(file, frequency_map)
+        })
     }
 
-    fn sort_directories(directories: &mut [DirectoryEntry]) {
-        directories.sort_by(|a, b| b.count.cmp(&a.count).then(a.path.len().cmp(&b.path.len())));
+    fn write_entries(file: &mut impl Write, frequency_map: &FrequencyMap) -> io::Result<()> {
+        writeln!(file, "{FREQUENCY_FILE_HEADER}{FREQUENCY_FILE_VERSION}")?;
+        for (path, entry) in frequency_map {
+            write!(
+                file,
+                "{}\t{}\t{}",
+                entry.count,
+                entry.last_access,
+                escape_field(path)
+            )?;
+            for (key, value) in &entry.metadata {
+                let key = escape_field(key).replace('=', "\\=");
+                write!(file, "\t{key}={}", escape_field(value))?;
+            }
+            writeln!(file)?;
+        }
+        Ok(())
     }
-}
 
-// Shell function installation and printing
-fn check_shell_compatibility() -> Result<(), Box<dyn Error>> {
-    let shell = env::var("SHELL").unwrap_or_else(|_| "unknown".to_string());
+    fn increment(path: &str) -> Result<(), CddError> {
+        Self::add(path, 1).map(|_| ())
+    }
 
-    if !shell.ends_with("/bash") && !shell.ends_with("bash") {
-        eprintln!("Error: Only bash is currently supported.");
-        eprintln!("Your current shell is: {shell}");
-        eprintln!("Please switch to bash or manually install the shell function.");
-        exit(1);
+    // Record `weight` visits to `path` now. Returns false, recording nothing, if the path
+    // is excluded.
+    fn add(path: &str, weight: u32) -> Result<bool, CddError> {
+        if Settings::load()?.exclusions()?.is_excluded(path) {
+            return Ok(false);
+        }
+        Self::update(|frequency_map| {
+            let entry = frequency_map.entry(path.to_string()).or_default();
+            entry.count = entry.count.saturating_add(weight);
+            entry.last_access = unix_now();
+            Self::age(frequency_map);
+        })?;
+        Ok(true)
     }
 
-    Ok(())
-}
+    // Drop entries whose directory has been missing for at least `grace`. A directory found
+    // missing for the first time is only marked, so briefly unmounted disks keep their
+    // history; the mark is cleared if the directory comes back.
+    fn clean(frequency_map: &mut FrequencyMap, grace: Duration, now: u64) -> CleanReport {
+        let mut report = CleanReport::default();
+        frequency_map.retain(|path, entry| {
+            if Path::new(path).is_dir() {
+                entry.metadata.remove(MISSING_SINCE_KEY);
+                return true;
+            }
 
-fn install_shell_function() -> Result<(), Box<dyn Error>> {
-    check_shell_compatibility()?;
+            let missing_since = entry
+                .metadata
+                .get(MISSING_SINCE_KEY)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(now);
+            if now.saturating_sub(missing_since) >= grace.as_secs() {
+                report.removed.push(path.clone());
+                return false;
+            }
+            entry
+                .metadata
+                .insert(MISSING_SINCE_KEY.to_string(), missing_since.to_string());
+            report.pending.push((path.clone(), missing_since));
+            true
+        });
+
+        report.removed.sort();
+        report.pending.sort();
+        report
+    }
 
-    let home = env::var("HOME").map_err(|_| "HOME environment variable not set")?;
-    let bashrc_path = Path::new(&home).join(".bashrc");
+    // Scale all counts down once their total passes MAX_TOTAL_COUNT, dropping entries that
+    // reach zero, so the file stays bounded and stale favourites eventually disappear
+    fn age(frequency_map: &mut FrequencyMap) {
+        let total: u64 = frequency_map.values().map(|entry| entry.count as u64).sum();
+        if total <= MAX_TOTAL_COUNT {
+            return;
+        }
 
-    let marker_start = "# BEGIN ccd function";
-    let marker_end = "# END ccd function";
+        let factor = 0.9 * MAX_TOTAL_COUNT as f64 / total as f64;
+        for entry in frequency_map.values_mut() {
+            entry.count = (entry.count as f64 * factor) as u32;
+        }
+        frequency_map.retain(|_, entry| entry.count > 0);
+    }
+}
 
-    // Create backup if .bashrc exists
-    if bashrc_path.exists() {
-        let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S");
-        let backup_path = format!("{}.backup.{}", bashrc_path.display(), timestamp);
-        fs::copy(&bashrc_path, &backup_path)?;
-        eprintln!("Created backup: {backup_path}");
+// Take an advisory lock on `path`, creating it and its directory if needed. The lock is
+// released when the returned file is dropped.
+fn lock_file(path: &Path) -> Result<fs::File, CddError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
     }
+    let lock_file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    lock_file.lock()?;
+    Ok(lock_file)
+}
 
-    // Read existing .bashrc content
-    let mut bashrc_content = if bashrc_path.exists() {
-        fs::read_to_string(&bashrc_path)?
-    } else {
-        String::new()
-    };
+// Write to a temporary file next to `path` and rename it into place, so readers and crashes
+// never see a half-written file
+fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<fs::File>) -> io::Result<()>,
+) -> Result<(), CddError> {
+    // Replace the target of a symlinked file (e.g. from a dotfiles repo), not the link
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let temp_file = path.with_extension(format!("tmp.{}", std::process::id()));
+
+    let result = fs::File::create(&temp_file)
+        .and_then(|file| {
+            let mut file = BufWriter::new(file);
+            write(&mut file)?;
+            file.flush()?;
+            file.get_ref().sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_file, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_file);
+    }
+    Ok(result?)
+}
 
-    // Remove existing ccd function if present
-    if bashrc_content.contains(marker_start) {
-        eprintln!("Removing existing ccd function from ~/.bashrc...");
-        let lines: Vec<&str> = bashrc_content.lines().collect();
-        let mut new_lines = Vec::new();
-        let mut skip = false;
-
-        for line in lines {
-            if line.contains(marker_start) {
-                skip = true;
-                continue;
+// Escape backslashes, tabs and newlines so any path fits in one tab-separated field. Metadata
+// keys also escape `=` as `\=`.
+fn escape_field(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_field(value: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => unescaped.push('\\'),
+            't' => unescaped.push('\t'),
+            'n' => unescaped.push('\n'),
+            'r' => unescaped.push('\r'),
+            '=' => unescaped.push('='),
+            _ => return None,
+        }
+    }
+    Some(unescaped)
+}
+
+// Split a "key=value" metadata field on the first `=` that isn't escaped; keys escape theirs
+fn split_metadata_field(field: &str) -> Option<(&str, &str)> {
+    let mut chars = field.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '=' => return Some((&field[..index], &field[index + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Utility function to check if locate command is available
+fn check_locate_available() -> bool {
+    Command::new("locate").arg("--version").output().is_ok()
+}
+
+// Why the locate backend has to run the locate command rather than reading its database,
+// which costs a process per search; None when it can read the database or isn't configured
+fn locate_database_notice(config: &SearchConfig) -> Option<String> {
+    if !config
+        .backends
+        .iter()
+        .any(|spec| spec.kind == BackendKind::Locate)
+    {
+        return None;
+    }
+    LocateDatabase::shared(config.locate_database.as_deref())
+        .err()
+        .map(|reason| {
+            format!("Running the locate command, as its database can't be read: {reason}")
+        })
+}
+
+// Print locate unavailable message with specified severity level
+fn print_locate_unavailable_message(severity: &str) {
+    eprintln!("{severity}: The 'locate' command is not available on this system.");
+    eprintln!("Search functionality will not work without it.");
+    eprintln!("Please install the 'plocate' package and run 'sudo updatedb'.");
+    eprintln!("Example: sudo apt install plocate && sudo updatedb");
+}
+
+// The merged results of one query. The interactive mode keeps it between searches, so that
+// loading more results continues the backends that stopped at the limit.
+struct SearchSession {
+    matcher: Matcher,
+    // Paths in first-seen order, each with the backends that produced it
+    found: Vec<(String, Vec<&'static str>)>,
+    index: HashMap<String, usize>,
+    files_filtered: usize,
+    // Backends that stopped at the limit, with the rest of their search
+    pending: Vec<(&'static str, Box<dyn Continuation>)>,
+}
+
+impl SearchSession {
+    fn new(matcher: &Matcher) -> Self {
+        Self {
+            matcher: matcher.clone(),
+            found: Vec::new(),
+            index: HashMap::new(),
+            files_filtered: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    // Merge the directories a backend found
+    fn add(&mut self, name: &'static str, output: BackendOutput) {
+        self.files_filtered += output.files_filtered;
+        for path in output.directories {
+            match self.index.get(&path) {
+                Some(&i) => {
+                    let sources = &mut self.found[i].1;
+                    if !sources.contains(&name) {
+                        sources.push(name);
+                    }
+                }
+                None => {
+                    self.index.insert(path.clone(), self.found.len());
+                    self.found.push((path, vec![name]));
+                }
             }
-            if line.contains(marker_end) {
-                skip = false;
+        }
+        if let Some(rest) = output.rest {
+            self.pending.push((name, rest));
+        }
+    }
+
+    // Ask each backend that stopped at the limit for up to `limit` more directories. A
+    // backend failing now only ends its share of the results.
+    fn more(
+        &mut self,
+        limit: usize,
+        frequency_map: &FrequencyMap,
+        cancel: &AtomicBool,
+    ) -> Result<SearchResult, CddError> {
+        for (name, rest) in std::mem::take(&mut self.pending) {
+            if let Ok(output) = rest.next(limit, cancel) {
+                self.add(name, output);
+            }
+        }
+        if cancel.load(Ordering::Relaxed) {
+            return Err(CddError::Cancelled);
+        }
+        Ok(self.result(frequency_map))
+    }
+
+    fn result(&self, frequency_map: &FrequencyMap) -> SearchResult {
+        DirectorySearcher::collect_result(
+            &self.found,
+            &self.matcher,
+            frequency_map,
+            self.files_filtered,
+            !self.pending.is_empty(),
+        )
+    }
+}
+
+// Directory search module
+struct DirectorySearcher;
+
+impl DirectorySearcher {
+    fn search(
+        matcher: &Matcher,
+        frequency_map: &FrequencyMap,
+        config: &SearchConfig,
+        limit: usize,
+    ) -> Result<SearchResult, CddError> {
+        let never_cancelled = AtomicBool::new(false);
+        let session = Self::search_streaming(
+            matcher,
+            frequency_map,
+            config,
+            limit,
+            &never_cancelled,
+            |_| {},
+        )?;
+        Ok(session.result(frequency_map))
+    }
+
+    // Like `search`, but reports the merged results so far after each backend finishes,
+    // gives up with CddError::Cancelled once `cancel` is set, and returns the session for
+    // loading more results
+    fn search_streaming(
+        matcher: &Matcher,
+        frequency_map: &FrequencyMap,
+        config: &SearchConfig,
+        limit: usize,
+        cancel: &AtomicBool,
+        mut on_progress: impl FnMut(SearchResult),
+    ) -> Result<SearchSession, CddError> {
+        let mut session = SearchSession::new(matcher);
+        let mut degraded = false;
+        let mut first_error = None;
+        let mut any_succeeded = false;
+
+        for spec in &config.backends {
+            // Fallbacks only run when nothing usable has been found so far
+            if spec.fallback && !session.found.is_empty() && !degraded {
                 continue;
             }
-            if !skip {
-                new_lines.push(line);
+
+            let backend = Self::build_backend(spec.kind, frequency_map, config);
+            match backend.search(matcher, limit, cancel) {
+                Ok(output) => {
+                    any_succeeded = true;
+                    degraded |= output.degraded;
+                    session.add(backend.name(), output);
+                }
+                Err(e) => {
+                    degraded = true;
+                    first_error.get_or_insert(e);
+                }
+            }
+
+            if cancel.load(Ordering::Relaxed) {
+                return Err(CddError::Cancelled);
+            }
+            if !session.found.is_empty() {
+                on_progress(session.result(frequency_map));
             }
         }
-        bashrc_content = new_lines.join("\n");
+
+        // If no directories found from any source, report why
+        if session.found.is_empty() {
+            return Err(match first_error {
+                Some(e) if !any_succeeded => e,
+                _ => CddError::NoDirectoriesFound,
+            });
+        }
+
+        Ok(session)
+    }
+
+    // Convert merged paths to sorted DirectoryEntry values with match and frequency data
+    fn collect_result(
+        found: &[(String, Vec<&'static str>)],
+        matcher: &Matcher,
+        frequency_map: &FrequencyMap,
+        files_filtered: usize,
+        more_available: bool,
+    ) -> SearchResult {
+        let mut directories: Vec<DirectoryEntry> = found
+            .iter()
+            .map(|(path, sources)| {
+                let frequency = frequency_map.get(path).cloned().unwrap_or_default();
+                // Paths from the search command need not match the pattern themselves
+                let found = matcher.score(path).unwrap_or_default();
+                DirectoryEntry::new(path.clone(), &frequency, sources.clone()).with_match(found)
+            })
+            .collect();
+
+        Self::sort_directories(&mut directories);
+        SearchResult::new(directories, files_filtered, more_available)
+    }
+
+    fn build_backend<'a>(
+        kind: BackendKind,
+        frequency_map: &'a FrequencyMap,
+        config: &'a SearchConfig,
+    ) -> Box<dyn SearchBackend + 'a> {
+        match kind {
+            BackendKind::Locate => Box::new(LocateBackend {
+                max_age: config.locate_max_age,
+                database: config.locate_database.as_ref(),
+                exclude: &config.exclude,
+            }),
+            BackendKind::Frequency => Box::new(FrequencyBackend {
+                frequency_map,
+                exclude: &config.exclude,
+            }),
+            BackendKind::Walk => Box::new(WalkBackend {
+                config: &config.walker,
+            }),
+            BackendKind::Fd => Box::new(FdBackend {
+                config: &config.walker,
+            }),
+            BackendKind::Find => Box::new(FindBackend {
+                config: &config.walker,
+            }),
+            BackendKind::Command => Box::new(CommandBackend {
+                command: config.search_command.as_deref().unwrap_or_default(),
+                exclude: &config.exclude,
+            }),
+        }
     }
 
-    // Add the new ccd function using eval
-    if !bashrc_content.is_empty() && !bashrc_content.ends_with('\n') {
-        bashrc_content.push('\n');
+    fn sort_directories(directories: &mut [DirectoryEntry]) {
+        // Sort by match score and frecency (descending), then by path length (ascending)
+        let now = unix_now();
+        directories.sort_by(|a, b| {
+            b.rank(now)
+                .total_cmp(&a.rank(now))
+                .then(a.path.len().cmp(&b.path.len()))
+        });
     }
+}
 
-    bashrc_content.push_str(&format!(
-        "\n{marker_start}\n# Shell wrapper for the ccd-pick command\n# This function is loaded dynamically from ccd-pick --printfn\n# This ensures the function stays up-to-date with ccd-pick updates\neval \"$(ccd-pick --printfn)\"\n\n{marker_end}\n"
+// Shell function installation and printing
+
+// Parse the arguments of --install and --printfn: an optional shell name and --track
+fn parse_shell_args(args: &[String]) -> Result<(Option<Shell>, bool), CddError> {
+    let mut shell = None;
+    let mut track = false;
+    for arg in args {
+        match arg.as_str() {
+            "--track" => track = true,
+            name if shell.is_none() && !name.starts_with('-') => shell = Some(Shell::parse(name)?),
+            other => return Err(CddError::Config(format!("unexpected argument '{other}'"))),
+        }
+    }
+    Ok((shell, track))
+}
+
+const INSTALL_MARKER_START: &str = "# BEGIN ccd function";
+const INSTALL_MARKER_END: &str = "# END ccd function";
+
+// Remove the block written by --install, returning None when there is none
+fn remove_install_block(content: &str) -> Option<String> {
+    if !content.contains(INSTALL_MARKER_START) {
+        return None;
+    }
+    let mut new_lines = Vec::new();
+    let mut skip = false;
+    for line in content.lines() {
+        if line.contains(INSTALL_MARKER_START) {
+            skip = true;
+            continue;
+        }
+        if line.contains(INSTALL_MARKER_END) {
+            skip = false;
+            continue;
+        }
+        if !skip {
+            new_lines.push(line);
+        }
+    }
+    Some(new_lines.join("\n"))
+}
+
+// Copy the startup file aside before changing it
+fn backup_rc_file(rc_path: &Path) -> Result<(), Box<dyn Error>> {
+    if rc_path.exists() {
+        let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S");
+        let backup_path = format!("{}.backup.{timestamp}", rc_path.display());
+        fs::copy(rc_path, &backup_path)?;
+        eprintln!("Created backup: {backup_path}");
+    }
+    Ok(())
+}
+
+fn install_shell_function(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (shell, track) = parse_shell_args(args)?;
+    let shell = match shell {
+        Some(shell) => shell,
+        None => Shell::detect()?,
+    };
+    let rc_path = shell.rc_file()?;
+    let rc_name = rc_path.display();
+
+    // Create backup if the startup file exists
+    backup_rc_file(&rc_path)?;
+
+    // Read existing startup file content
+    let mut rc_content = if rc_path.exists() {
+        fs::read_to_string(&rc_path)?
+    } else {
+        String::new()
+    };
+
+    // Remove existing ccd function if present
+    if let Some(content) = remove_install_block(&rc_content) {
+        eprintln!("Removing existing ccd function from {rc_name}...");
+        rc_content = content;
+    }
+
+    // Add the new ccd function, loaded from ccd-pick --printfn
+    if !rc_content.is_empty() && !rc_content.ends_with('\n') {
+        rc_content.push('\n');
+    }
+
+    let track_arg = if track { " --track" } else { "" };
+    let printfn = format!("--printfn {}{track_arg}", shell.name());
+    let load_command = shell.load_command(&printfn)?;
+    rc_content.push_str(&format!(
+        "\n{INSTALL_MARKER_START}\n# Shell wrapper for the ccd-pick command\n# This function is loaded dynamically from ccd-pick {printfn}\n# This ensures the function stays up-to-date with ccd-pick updates\n{load_command}\n\n{INSTALL_MARKER_END}\n"
     ));
 
-    // Write the updated .bashrc
-    fs::write(&bashrc_path, bashrc_content)?;
+    // Write the updated startup file (fish's conf.d may not exist yet)
+    if let Some(parent) = rc_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&rc_path, rc_content)?;
 
-    eprintln!("Successfully installed ccd function to ~/.bashrc");
+    eprintln!("Successfully installed ccd function to {rc_name}");
     eprintln!();
     eprintln!("To use ccd immediately, run:");
-    eprintln!("  source ~/.bashrc");
+    eprintln!("  source {rc_name}");
     eprintln!();
     eprintln!("Or start a new terminal session.");
     eprintln!();
@@ -570,11 +1611,81 @@ fn install_shell_function() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn print_shell_function() {
-    // Skip the first line (shebang) and print the rest
-    if let Some(pos) = CCD_SHELL_FUNCTION.find('\n') {
-        println!("{}", &CCD_SHELL_FUNCTION[pos + 1..]);
+// Undo --install for the given or detected shell. fish's file only holds the ccd block, so it
+// is deleted once the block is gone.
+fn uninstall_shell_function(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (shell, track) = parse_shell_args(args)?;
+    if track {
+        return Err(CddError::Config("unexpected argument '--track'".to_string()).into());
+    }
+    let shell = match shell {
+        Some(shell) => shell,
+        None => Shell::detect()?,
+    };
+    let rc_path = shell.rc_file()?;
+    let rc_name = rc_path.display();
+
+    let rc_content = match fs::read_to_string(&rc_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(mut rc_content) = remove_install_block(&rc_content) else {
+        eprintln!("The ccd function is not installed in {rc_name}");
+        return Ok(());
+    };
+
+    backup_rc_file(&rc_path)?;
+    if shell == Shell::Fish && rc_content.trim().is_empty() {
+        fs::remove_file(&rc_path)?;
+    } else {
+        // Drop the blank line --install put before the block
+        rc_content.truncate(rc_content.trim_end().len());
+        if !rc_content.is_empty() {
+            rc_content.push('\n');
+        }
+        fs::write(&rc_path, rc_content)?;
     }
+
+    eprintln!("Removed the ccd function from {rc_name}");
+    eprintln!("Start a new terminal session for the change to take effect.");
+    Ok(())
+}
+
+// Print the ccd function for the given or detected shell (bash if unknown), followed by the
+// directory tracking hook if --track is given
+fn print_shell_function(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (shell, track) = parse_shell_args(args)?;
+    let shell = shell.unwrap_or_else(|| Shell::detect().unwrap_or(Shell::Bash));
+    let hook = if track { Hook::Prompt } else { Hook::None };
+    print!("{}", shell.init_script("ccd", hook));
+    Ok(())
+}
+
+// Print the wrapper function for a shell, named by --cmd and with the tracking hook chosen by
+// --hook (none by default)
+fn init_shell(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut shell = None;
+    let mut cmd = "ccd";
+    let mut hook = Hook::None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cmd" => cmd = option_value(&mut args, "--cmd")?,
+            "--hook" => hook = Hook::parse(option_value(&mut args, "--hook")?)?,
+            name if shell.is_none() && !name.starts_with('-') => shell = Some(Shell::parse(name)?),
+            other => {
+                return Err(CddError::Config(format!("unexpected argument '{other}'")).into());
+            }
+        }
+    }
+    let shell = shell.ok_or_else(|| {
+        CddError::Config("init requires a shell (bash, zsh, fish or nushell)".to_string())
+    })?;
+    Shell::validate_command_name(cmd)?;
+
+    print!("{}", shell.init_script(cmd, hook));
+    Ok(())
 }
 
 // Main application logic
@@ -590,58 +1701,644 @@ fn run() -> Result<(), Box<dyn Error>> {
 
     match args.len() {
         1 => print_help(),
+        // Everything after "--" is a search, even words like "list" or "-i"
+        _ if args[1] == "--" => match &args[2..] {
+            [] => return Err(CddError::Config("-- needs a search pattern".to_string()).into()),
+            terms => search_and_change_directory(&terms.join(" "))?,
+        },
+        _ if args[1] == "--export" => export_frequency(&args[2..])?,
+        _ if args[1] == "--import-file" => import_frequency_file(&args[2..])?,
+        _ if args[1] == "--merge-files" => merge_frequency_files(&args[2..])?,
+        _ if args[1] == "--clean" => clean_frequency_file(&args[2..])?,
+        _ if args[1] == "--install" => install_shell_function(&args[2..])?,
+        _ if args[1] == "--uninstall" => uninstall_shell_function(&args[2..])?,
+        _ if args[1] == "--printfn" => print_shell_function(&args[2..])?,
+        _ if args[1] == "init" => init_shell(&args[2..])?,
+        _ if args[1] == "list" => list_entries(&args[2..])?,
+        _ if args[1] == "remove" => remove_entries(&args[2..])?,
+        _ if args[1] == "set" => set_entry(&args[2..])?,
+        _ if args[1] == "add" => add_entry(&args[2..])?,
+        _ if args[1] == "--regex" || args[1] == "--glob" => {
+            // Same as typing the re: or glob: prefix
+            let prefix = if args[1] == "--regex" { "re:" } else { "glob:" };
+            match &args[2..] {
+                [pattern] => search_and_change_directory(&format!("{prefix}{pattern}"))?,
+                _ => return Err(CddError::Config(format!("{} takes one pattern", args[1])).into()),
+            }
+        }
         2 => match args[1].as_str() {
             "-i" => run_interactive_mode()?,
-            "-b" | "--bookmark" => bookmark_current_directory()?,
+            "-b" | "--bookmark" => bookmark_current_directory(None)?,
+            "--bookmarks" => list_bookmarks()?,
             "--help" | "-h" => print_help(),
-            "--install" => install_shell_function()?,
-            "--printfn" => print_shell_function(),
-            pattern => search_and_change_directory(pattern)?,
+            pattern => match pattern.strip_prefix('@') {
+                Some(name) => jump_to_bookmark(name)?,
+                None => search_and_change_directory(pattern)?,
+            },
         },
+        3 if args[1] == "-b" || args[1] == "--bookmark" => {
+            bookmark_current_directory(Some(&args[2]))?;
+        }
+        3 if args[1] == "--remove-bookmark" => remove_bookmark(&args[2])?,
+        3 if args[1] == "--track" => track_directory(&args[2])?,
         3 if args[1] == "--increment" => {
             FrequencyManager::increment(&args[2])?;
         }
+        3 | 4 if args[1] == "--import" => {
+            import_history(&args[2], args.get(3).map(Path::new))?;
+        }
         _ => {
-            let pattern = &args[1];
-            search_and_change_directory(pattern)?;
+            // Every word is a term of the query
+            let pattern = args[1..].join(" ");
+            search_and_change_directory(&pattern)?;
         }
     }
 
     Ok(())
 }
 
-fn bookmark_current_directory() -> Result<(), Box<dyn Error>> {
+// Save the current directory as a named bookmark, named after the directory by default
+fn bookmark_current_directory(name: Option<&str>) -> Result<(), Box<dyn Error>> {
     let current_dir = env::current_dir()
         .map_err(CddError::IoError)?
         .to_string_lossy()
         .to_string();
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => Path::new(&current_dir)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| CddError::Config("name the bookmark: ccd-pick -b <name>".to_string()))?,
+    };
+    BookmarkManager::validate_name(&name)?;
+
+    let previous =
+        BookmarkManager::update(|bookmarks| bookmarks.insert(name.clone(), current_dir.clone()))?;
+
+    match previous {
+        Some(previous) if previous != current_dir => {
+            eprintln!("Bookmark @{name} moved from {previous} to {current_dir}")
+        }
+        _ => eprintln!("Bookmarked @{name}: {current_dir}"),
+    }
+
+    Ok(())
+}
+
+fn list_bookmarks() -> Result<(), Box<dyn Error>> {
+    for (name, path) in BookmarkManager::load()? {
+        println!("@{name}\t{path}");
+    }
+    Ok(())
+}
+
+fn remove_bookmark(name: &str) -> Result<(), Box<dyn Error>> {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    match BookmarkManager::update(|bookmarks| bookmarks.remove(name))? {
+        Some(path) => eprintln!("Removed bookmark @{name} ({path})"),
+        None => return Err(CddError::Config(format!("no bookmark named '{name}'")).into()),
+    }
+    Ok(())
+}
+
+// Print the directory of bookmark `name` for the shell wrapper to cd into
+fn jump_to_bookmark(name: &str) -> Result<(), Box<dyn Error>> {
+    let bookmarks = BookmarkManager::load()?;
+    let path = bookmarks
+        .get(name)
+        .ok_or_else(|| CddError::Config(format!("no bookmark named '{name}'")))?;
+    if !Path::new(path).is_dir() {
+        return Err(CddError::DirectoryNotFound(path.clone()).into());
+    }
+
+    println!("{path}");
+    eprintln!("Bookmark @{name}: {path}");
+    Ok(())
+}
+
+fn import_history(tool: &str, file: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let source = ImportSource::parse(tool)?;
+    let file = match file {
+        Some(file) => file.to_path_buf(),
+        None => source.default_path()?,
+    };
+    let entries = source.read(&file).map_err(|e| match e {
+        CddError::IoError(e) => CddError::Config(format!("{}: {e}", file.display())),
+        other => other,
+    })?;
+    let exclude = Settings::load()?.exclusions()?;
+
+    // Existing entries keep their own history: counts are added and the later access wins
+    let (added, merged, skipped, excluded) = FrequencyManager::update(|frequency_map| {
+        let (mut added, mut merged, mut skipped, mut excluded) = (0, 0, 0, 0);
+        for imported in entries {
+            if exclude.is_excluded(&imported.path) {
+                excluded += 1;
+                continue;
+            }
+            if !Path::new(&imported.path).is_dir() {
+                skipped += 1;
+                continue;
+            }
+            let last_access = imported.last_access.unwrap_or(0);
+            match frequency_map.get_mut(&imported.path) {
+                Some(entry) => {
+                    entry.count = entry.count.saturating_add(imported.count);
+                    entry.last_access = entry.last_access.max(last_access);
+                    merged += 1;
+                }
+                None => {
+                    let entry = FrequencyEntry::new(imported.count, last_access);
+                    frequency_map.insert(imported.path, entry);
+                    added += 1;
+                }
+            }
+        }
+        FrequencyManager::age(frequency_map);
+        (added, merged, skipped, excluded)
+    })?;
+
+    eprintln!(
+        "Imported from {} ({}): {added} added, {merged} merged, {skipped} skipped (missing or not a directory), {excluded} excluded",
+        source.name(),
+        file.display()
+    );
+    Ok(())
+}
+
+// Value of an option such as `--format json`
+fn option_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    name: &str,
+) -> Result<&'a str, CddError> {
+    args.next()
+        .map(String::as_str)
+        .ok_or_else(|| CddError::Config(format!("{name} requires a value")))
+}
+
+fn export_frequency(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut format = ExportFormat::Json;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = ExportFormat::parse(option_value(&mut args, "--format")?)?,
+            other => {
+                return Err(CddError::Config(format!("unexpected argument '{other}'")).into());
+            }
+        }
+    }
 
-    let mut frequency_map = FrequencyManager::load()?;
+    let frequency_map = FrequencyManager::load()?;
+    io::stdout().write_all(transfer::export(&frequency_map, format).as_bytes())?;
+    Ok(())
+}
+
+fn import_frequency_file(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut file = None;
+    let mut format = None;
+    let mut replace = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = Some(ExportFormat::parse(option_value(&mut args, "--format")?)?),
+            "--replace" => replace = true,
+            "--merge" => replace = false,
+            path if file.is_none() => file = Some(path),
+            other => {
+                return Err(CddError::Config(format!("unexpected argument '{other}'")).into());
+            }
+        }
+    }
+
+    let file = file.ok_or_else(|| CddError::Config("--import-file requires a file".to_string()))?;
+    let format = format
+        .or_else(|| ExportFormat::from_extension(Path::new(file)))
+        .ok_or_else(|| {
+            CddError::Config(format!("cannot tell the format of '{file}', pass --format"))
+        })?;
+    // "-" reads from standard input, for piping between machines
+    let content = if file == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(file)
+    }
+    .map_err(|e| CddError::Import(format!("{file}: {e}")))?;
+
+    let mut imported = transfer::import(&content, format)?;
+    let exclude = Settings::load()?.exclusions()?;
+    let read = imported.len();
+    imported.retain(|path, _| !exclude.is_excluded(path));
+    let excluded = read - imported.len();
+    let total = imported.len();
+    if replace {
+        FrequencyManager::update(|frequency_map| *frequency_map = imported)?;
+        eprintln!(
+            "Replaced the frequency database with {total} entries from {file} ({excluded} excluded)"
+        );
+    } else {
+        let (added, merged) = FrequencyManager::update(|frequency_map| {
+            let counts = transfer::merge(frequency_map, imported, MergeStrategy::Sum);
+            FrequencyManager::age(frequency_map);
+            counts
+        })?;
+        eprintln!(
+            "Imported {total} entries from {file}: {added} added, {merged} merged, {excluded} excluded"
+        );
+    }
+    Ok(())
+}
+
+// Merge frequency files from other machines into ours, moving their paths with rewrite rules
+fn merge_frequency_files(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut files = Vec::new();
+    let mut strategy = MergeStrategy::Sum;
+    let mut rules = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strategy" => {
+                strategy = MergeStrategy::parse(option_value(&mut args, "--strategy")?)?
+            }
+            "--rewrite" => rules.push(RewriteRule::parse(option_value(&mut args, "--rewrite")?)?),
+            other if other.starts_with("--") => {
+                return Err(CddError::Config(format!("unexpected argument '{other}'")).into());
+            }
+            file => files.push(PathBuf::from(file)),
+        }
+    }
+    if files.is_empty() {
+        return Err(
+            CddError::Config("--merge-files requires at least one file".to_string()).into(),
+        );
+    }
+
+    // Read and rewrite everything first, so a bad file leaves our database untouched
+    let exclude = Settings::load()?.exclusions()?;
+    let mut incoming = FrequencyMap::new();
+    let mut rewritten = 0;
+    let mut excluded = 0;
+    for file in &files {
+        let mut entries = FrequencyMap::new();
+        for (path, entry) in FrequencyManager::read_file(file)? {
+            let path = match transfer::rewrite_path(&rules, &path) {
+                Some(new_path) => {
+                    rewritten += 1;
+                    new_path
+                }
+                None => path,
+            };
+            // Exclusions apply to paths as they are on this machine
+            if exclude.is_excluded(&path) {
+                excluded += 1;
+                continue;
+            }
+            // Entries that now share a path were distinct directories, so always add them up
+            match entries.get_mut(&path) {
+                Some(existing) => transfer::combine(existing, entry, MergeStrategy::Sum),
+                None => {
+                    entries.insert(path, entry);
+                }
+            }
+        }
+        transfer::merge(&mut incoming, entries, strategy);
+    }
+
+    let (added, merged) = FrequencyManager::update(|frequency_map| {
+        let counts = transfer::merge(frequency_map, incoming, strategy);
+        FrequencyManager::age(frequency_map);
+        counts
+    })?;
+    eprintln!(
+        "Merged {} files: {added} added, {merged} merged, {rewritten} paths rewritten, {excluded} excluded",
+        files.len()
+    );
+    Ok(())
+}
+
+// Resolve a path given on the command line to the form stored in the frequency file:
+// relative paths are made absolute, and trailing slashes are dropped
+fn resolve_path_arg(path: &str) -> Result<String, CddError> {
+    let resolved = if Path::new(path).is_absolute() {
+        PathBuf::from(path)
+    } else {
+        fs::canonicalize(path).map_err(|_| CddError::DirectoryNotFound(path.to_string()))?
+    };
+    let resolved = resolved.to_string_lossy();
+    match resolved.trim_end_matches('/') {
+        "" => Ok("/".to_string()),
+        trimmed => Ok(trimmed.to_string()),
+    }
+}
+
+fn format_last_access(last_access: u64) -> String {
+    chrono::DateTime::from_timestamp(last_access as i64, 0)
+        .filter(|_| last_access > 0)
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|| "never".to_string())
+}
+
+// Record a directory reached by any means. Called by the shell hook on every directory
+// change, so it stays quiet about directories it can't record.
+fn track_directory(path: &str) -> Result<(), Box<dyn Error>> {
+    let path = resolve_path_arg(path)?;
+    let settings = Settings::load()?;
+    let weight = match settings.get("track_weight") {
+        Some(value) => parse_number("track_weight", &value)?,
+        None => DEFAULT_TRACK_WEIGHT,
+    };
+    if weight == 0 || settings.exclusions()?.is_excluded(&path) || !Path::new(&path).is_dir() {
+        return Ok(());
+    }
+
+    let now = unix_now();
+    FrequencyManager::update(|frequency_map| {
+        let entry = frequency_map.entry(path).or_default();
+        // A visit that was just recorded, e.g. by picking the directory in the TUI, counts once
+        if now.saturating_sub(entry.last_access) < TRACK_DEDUPE_SECS {
+            return;
+        }
+        entry.count = entry.count.saturating_add(weight);
+        entry.last_access = now;
+        FrequencyManager::age(frequency_map);
+    })?;
+    Ok(())
+}
 
-    // Only add if not already in the frequency map
-    if !frequency_map.contains_key(&current_dir) {
-        frequency_map.insert(current_dir.clone(), 1);
-        FrequencyManager::save(&frequency_map)?;
-        eprintln!("Bookmarked: {current_dir}");
+fn list_entries(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut sort = "frecency";
+    let mut format = "plain";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sort" => sort = option_value(&mut args, "--sort")?,
+            "--format" => format = option_value(&mut args, "--format")?,
+            other => {
+                return Err(CddError::Config(format!("unexpected argument '{other}'")).into());
+            }
+        }
+    }
+
+    let frequency_map = FrequencyManager::load()?;
+    let now = unix_now();
+    let mut entries: Vec<_> = frequency_map.iter().collect();
+    match sort {
+        "frecency" => entries.sort_by(|a, b| {
+            frecency(b.1.count, b.1.last_access, now)
+                .total_cmp(&frecency(a.1.count, a.1.last_access, now))
+                .then_with(|| a.0.cmp(b.0))
+        }),
+        "count" => entries.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0))),
+        "recent" => entries.sort_by(|a, b| {
+            b.1.last_access
+                .cmp(&a.1.last_access)
+                .then_with(|| a.0.cmp(b.0))
+        }),
+        "path" => entries.sort_by(|a, b| a.0.cmp(b.0)),
+        other => {
+            return Err(CddError::Config(format!(
+                "unknown sort order '{other}' (expected frecency, count, recent or path)"
+            ))
+            .into());
+        }
+    }
+
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    match format {
+        "plain" => {
+            for (path, entry) in entries {
+                let last_access = format_last_access(entry.last_access);
+                writeln!(stdout, "{:>6}  {last_access:<16}  {path}", entry.count)?;
+            }
+        }
+        "tsv" => {
+            for (path, entry) in entries {
+                let path = escape_field(path);
+                writeln!(stdout, "{}\t{}\t{path}", entry.count, entry.last_access)?;
+            }
+        }
+        "json" => {
+            let entries: Vec<serde_json::Value> = entries
+                .into_iter()
+                .map(|(path, entry)| {
+                    serde_json::json!({
+                        "path": path,
+                        "count": entry.count,
+                        "last_access": entry.last_access,
+                        "frecency": frecency(entry.count, entry.last_access, now),
+                    })
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut stdout, &entries)?;
+            writeln!(stdout)?;
+        }
+        other => {
+            return Err(CddError::Config(format!(
+                "unknown format '{other}' (expected plain, tsv or json)"
+            ))
+            .into());
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+// Remove one recorded path, or every path containing a pattern
+fn remove_entries(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut target = None;
+    let mut dry_run = false;
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" | "-n" => dry_run = true,
+            other if target.is_none() => target = Some(other),
+            other => {
+                return Err(CddError::Config(format!("unexpected argument '{other}'")).into());
+            }
+        }
+    }
+    let target = target
+        .filter(|target| !target.is_empty())
+        .ok_or_else(|| CddError::Config("remove requires a path or pattern".to_string()))?;
+    // Something that looks like a path only ever removes that directory, so that a typo such
+    // as `remove /` can't forget every entry
+    let path_only = target.starts_with('/') || target.starts_with('.');
+
+    // An exact path (also in relative form) wins; anything else is a substring pattern
+    let select = |frequency_map: &FrequencyMap| -> Vec<String> {
+        let resolved = resolve_path_arg(target).ok();
+        let exact = [Some(target.to_string()), resolved]
+            .into_iter()
+            .flatten()
+            .find(|path| frequency_map.contains_key(path));
+        let mut paths: Vec<String> = match exact {
+            Some(path) => vec![path],
+            None if path_only => Vec::new(),
+            None => frequency_map
+                .keys()
+                .filter(|path| path.contains(target))
+                .cloned()
+                .collect(),
+        };
+        paths.sort();
+        paths
+    };
+
+    let removed = if dry_run {
+        select(&FrequencyManager::load()?)
     } else {
-        eprintln!("Directory already bookmarked: {current_dir}");
+        FrequencyManager::update(|frequency_map| {
+            let paths = select(frequency_map);
+            for path in &paths {
+                frequency_map.remove(path);
+            }
+            paths
+        })?
+    };
+
+    if removed.is_empty() && path_only {
+        return Err(CddError::Config(format!("{target} is not a recorded directory")).into());
+    }
+    if removed.is_empty() {
+        return Err(CddError::Config(format!("no recorded directory matches '{target}'")).into());
+    }
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    eprintln!("{verb} {} entries", removed.len());
+    for path in removed {
+        eprintln!("  {path}");
+    }
+    Ok(())
+}
+
+// Set the count of a path outright; a count of 0 forgets it
+fn set_entry(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let [path, count] = args else {
+        return Err(CddError::Config("usage: ccd-pick set <path> <count>".to_string()).into());
+    };
+    let path = resolve_path_arg(path)?;
+    let count: u32 = parse_number("count", count)?;
+    // Setting a count records the directory, so it is checked like `add`; 0 removes it
+    if count > 0 {
+        if !Path::new(&path).is_dir() {
+            return Err(CddError::DirectoryNotFound(path).into());
+        }
+        if Settings::load()?.exclusions()?.is_excluded(&path) {
+            return Err(CddError::Config(format!("{path} is excluded from the history")).into());
+        }
     }
 
+    FrequencyManager::update(|frequency_map| {
+        if count == 0 {
+            frequency_map.remove(&path);
+            return;
+        }
+        let entry = frequency_map
+            .entry(path.clone())
+            .or_insert_with(|| FrequencyEntry::new(0, unix_now()));
+        entry.count = count;
+    })?;
+
+    if count == 0 {
+        eprintln!("Removed {path}");
+    } else {
+        eprintln!("Set {path} to {count}");
+    }
+    Ok(())
+}
+
+fn add_entry(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut path = None;
+    let mut weight = 1;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--weight" => weight = parse_number("--weight", option_value(&mut args, "--weight")?)?,
+            other if path.is_none() => path = Some(other),
+            other => {
+                return Err(CddError::Config(format!("unexpected argument '{other}'")).into());
+            }
+        }
+    }
+    let path = path.ok_or_else(|| CddError::Config("add requires a path".to_string()))?;
+    let path = resolve_path_arg(path)?;
+    if !Path::new(&path).is_dir() {
+        return Err(CddError::DirectoryNotFound(path).into());
+    }
+
+    if !FrequencyManager::add(&path, weight)? {
+        return Err(CddError::Config(format!("{path} is excluded from the history")).into());
+    }
+    eprintln!("Added {path} with weight {weight}");
+    Ok(())
+}
+
+fn clean_frequency_file(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut grace = CleanConfig::load()?.grace;
+    let mut dry_run = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" | "-n" => dry_run = true,
+            "--grace" => {
+                let days: u64 = parse_number("--grace", option_value(&mut args, "--grace")?)?;
+                grace = Duration::from_secs(days * DAY);
+            }
+            other => {
+                return Err(CddError::Config(format!("unexpected argument '{other}'")).into());
+            }
+        }
+    }
+
+    let now = unix_now();
+    let report = if dry_run {
+        FrequencyManager::clean(&mut FrequencyManager::load()?, grace, now)
+    } else {
+        FrequencyManager::update(|frequency_map| {
+            FrequencyManager::clean(frequency_map, grace, now)
+        })?
+    };
+
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    eprintln!("{verb} {} missing directories", report.removed.len());
+    for path in &report.removed {
+        eprintln!("  {path}");
+    }
+    if !report.pending.is_empty() {
+        eprintln!(
+            "Keeping {} missing directories for the {}-day grace period",
+            report.pending.len(),
+            grace.as_secs() / DAY
+        );
+        for (path, missing_since) in &report.pending {
+            let remaining = (missing_since + grace.as_secs()).saturating_sub(now);
+            eprintln!("  {path} (removed in {} days)", remaining.div_ceil(DAY));
+        }
+    }
     Ok(())
 }
 
 fn search_and_change_directory(search_pattern: &str) -> Result<(), Box<dyn Error>> {
     eprintln!("Searching for directories matching: {search_pattern}");
 
+    let config = SearchConfig::load()?;
     let frequency_map = FrequencyManager::load()?;
+    let matcher = Matcher::new(search_pattern, config.case)?;
     let search_result =
-        DirectorySearcher::search(search_pattern, &frequency_map).map_err(|e| match e {
-            CddError::NoDirectoriesFound => {
-                eprintln!("No directories found matching '{search_pattern}'");
-                exit(1);
-            }
-            other => other,
-        })?;
+        DirectorySearcher::search(&matcher, &frequency_map, &config, config.result_limit).map_err(
+            |e| match e {
+                CddError::NoDirectoriesFound => {
+                    eprintln!("No directories found matching '{search_pattern}'");
+                    exit(1);
+                }
+                CddError::LocateCommand(_) => {
+                    print_locate_unavailable_message("ERROR");
+                    exit(1);
+                }
+                other => other,
+            },
+        )?;
 
     let target_dir = &search_result.directories[0].path;
 
@@ -670,10 +2367,16 @@ fn search_and_change_directory(search_pattern: &str) -> Result<(), Box<dyn Error
         String::new()
     };
 
+    let more_info = if search_result.more_available {
+        " (more available)"
+    } else {
+        ""
+    };
+
     eprintln!(
-        "Found {} directories in first {} results{}, selected: {}{}",
+        "Found {} directories{}{}, selected: {}{}",
         search_result.directories.len(),
-        LOCATE_LIMIT,
+        more_info,
         files_info,
         target_dir,
         freq_info
@@ -683,21 +2386,38 @@ fn search_and_change_directory(search_pattern: &str) -> Result<(), Box<dyn Error
 }
 
 fn run_interactive_mode() -> Result<(), Box<dyn Error>> {
-    // Check if locate is available before setting up the TUI
-    if !check_locate_available() {
+    let config = SearchConfig::load()?;
+
+    // When locate is the only configured backend, make sure it exists before setting up the TUI
+    let locate_only = config
+        .backends
+        .iter()
+        .all(|spec| spec.kind == BackendKind::Locate);
+    if locate_only
+        && LocateDatabase::shared(config.locate_database.as_deref()).is_err()
+        && !check_locate_available()
+    {
         print_locate_unavailable_message("ERROR");
         exit(1);
     }
 
-    // Setup terminal
+    // Load the frequency file before taking over the terminal, so warnings stay readable
+    let mut app = App::new(config)?;
+
+    // Setup terminal. When standard output is captured rather than a terminal (as by the nushell
+    // wrapper), draw on /dev/tty so the selection can be printed to standard output.
+    let captured = !io::stdout().is_terminal();
+    let mut screen: Box<dyn Write> = if !captured {
+        Box::new(io::stdout())
+    } else {
+        Box::new(fs::OpenOptions::new().write(true).open("/dev/tty")?)
+    };
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    execute!(screen, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(screen);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app and run
-    let mut app = App::new()?;
+    // Run app
     let res = run_app(&mut terminal, &mut app);
 
     // Restore terminal
@@ -719,8 +2439,17 @@ fn run_interactive_mode() -> Result<(), Box<dyn Error>> {
                 // Increment frequency count for the selected directory
                 FrequencyManager::increment(selected_dir)?;
 
-                // Output the selected directory to file descriptor 3 if available, otherwise stdout
-                if let Ok(mut fd3) = fs::OpenOptions::new().write(true).open("/proc/self/fd/3") {
+                // Output the selected directory to file descriptor 3 if available, otherwise stdout.
+                // With captured output, fd 3 may be the /dev/tty opened above.
+                let fd3 = if captured {
+                    None
+                } else {
+                    fs::OpenOptions::new()
+                        .write(true)
+                        .open("/proc/self/fd/3")
+                        .ok()
+                };
+                if let Some(mut fd3) = fd3 {
                     writeln!(fd3, "{selected_dir}")?;
                 } else {
                     println!("{selected_dir}");
@@ -741,23 +2470,30 @@ fn run_app<B: ratatui::backend::Backend>(
     app: &mut App,
 ) -> io::Result<()> {
     loop {
+        app.apply_search_updates();
         terminal.draw(|f| ui(f, app))?;
 
+        // Wake up regularly so results from the background search get drawn
+        if !event::poll(TICK_RATE)? {
+            continue;
+        }
+
         if let Event::Key(key) = event::read()? {
             if key.kind == KeyEventKind::Press {
                 match key.code {
                     KeyCode::Esc => {
                         app.should_quit = true;
                     }
-                    KeyCode::Enter => {
-                        if app.get_selected_directory().is_some() {
-                            app.user_selected = true;
-                            app.should_quit = true;
-                        }
+                    KeyCode::Enter if app.get_selected_directory().is_some() => {
+                        app.user_selected = true;
+                        app.should_quit = true;
                     }
                     KeyCode::Tab => {
                         app.toggle_view_mode();
                     }
+                    KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.toggle_case_mode();
+                    }
                     KeyCode::Char(c) => {
                         app.handle_character_input(c);
                     }
@@ -786,19 +2522,25 @@ fn run_app<B: ratatui::backend::Backend>(
 }
 
 fn ui(f: &mut Frame, app: &App) {
+    let notice_height = if app.notice.is_some() { 1 } else { 0 };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
-            Constraint::Length(3), // Input box
-            Constraint::Min(0),    // Results list
-            Constraint::Length(3), // Help text
+            Constraint::Length(3),             // Input box
+            Constraint::Min(0),                // Results list
+            Constraint::Length(notice_height), // Status line
+            Constraint::Length(3),             // Help text
         ])
         .split(f.area());
 
     render_input_box(f, app, chunks[0]);
     render_results_list(f, app, chunks[1]);
-    render_help_text(f, chunks[2]);
+    if let Some(notice) = &app.notice {
+        let status = Paragraph::new(notice.as_str()).style(Style::default().fg(Color::DarkGray));
+        f.render_widget(status, chunks[2]);
+    }
+    render_help_text(f, chunks[3]);
 
     // Set cursor position in input box
     f.set_cursor_position((chunks[0].x + app.input.len() as u16 + 1, chunks[0].y + 1));
@@ -808,8 +2550,9 @@ fn render_input_box(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     let (input_text, input_style) = if app.input.is_empty() {
         let placeholder = match app.view_mode {
             ViewMode::Search => "Start typing or press [Tab] to see frequent choices",
-            ViewMode::Frequent => {
-                "Search the list below, or press [Tab] to search across all directories"
+            ViewMode::Frequent => "Search the list below, or press [Tab] to see bookmarks",
+            ViewMode::Bookmarks => {
+                "Search bookmarks by name or path, or press [Tab] to search all directories"
             }
         };
         (placeholder, Style::default().fg(Color::DarkGray))
@@ -820,8 +2563,10 @@ fn render_input_box(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     let title = match app.view_mode {
         ViewMode::Search => "Search All Directories",
         ViewMode::Frequent => "Search Frequently Used",
+        ViewMode::Bookmarks => "Search Bookmarks",
     };
 
+    let title = format!("{title} ({})", app.case_mode.name());
     let block = Block::default()
         .borders(Borders::ALL)
         .title(Span::styled(title, Style::default().fg(Color::Gray)))
@@ -832,31 +2577,45 @@ fn render_input_box(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
 }
 
 fn render_results_list(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
-    let items: Vec<ListItem> = if app.directories.is_empty() && app.view_mode == ViewMode::Frequent
-    {
-        vec![ListItem::new(Line::from(Span::styled(
-            "No frequently used directories found",
-            Style::default()
-                .fg(Color::DarkGray)
-                .add_modifier(Modifier::ITALIC),
-        )))]
-    } else {
-        app.directories
-            .iter()
-            .map(|dir| create_list_item(dir))
-            .collect()
+    let empty_message = match app.view_mode {
+        ViewMode::Search => None,
+        ViewMode::Frequent => Some("No frequently used directories found"),
+        ViewMode::Bookmarks => Some("No bookmarks found; add one with: ccd -b <name>"),
     };
+    let items: Vec<ListItem> =
+        if let Some(message) = empty_message.filter(|_| app.directories.is_empty()) {
+            vec![ListItem::new(Line::from(Span::styled(
+                message,
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::ITALIC),
+            )))]
+        } else {
+            let show_sources = app.view_mode == ViewMode::Search;
+            app.directories
+                .iter()
+                .map(|dir| create_list_item(dir, show_sources))
+                .collect()
+        };
 
     let title = match app.view_mode {
         ViewMode::Search => {
-            if app.files_filtered > 0 {
+            let more = if app.more_available { "+" } else { "" };
+            let title = if app.files_filtered > 0 {
                 format!(
-                    "Search Results ({} found; {} matching files not shown)",
+                    "Search Results ({}{} found; {} matching files not shown)",
                     app.directories.len(),
+                    more,
                     app.files_filtered
                 )
             } else {
-                format!("Search Results ({} found)", app.directories.len())
+                format!("Search Results ({}{} found)", app.directories.len(), more)
+            };
+            if app.searching {
+                let spinner = SPINNER_FRAMES[app.spinner_frame % SPINNER_FRAMES.len()];
+                format!("{title} {spinner} searching...")
+            } else {
+                title
             }
         }
         ViewMode::Frequent => {
@@ -866,6 +2625,17 @@ fn render_results_list(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
                 format!("Frequent Directories ({} found)", app.directories.len())
             }
         }
+        ViewMode::Bookmarks => format!("Bookmarks ({} found)", app.directories.len()),
+    };
+
+    // An invalid pattern or a failed search replaces the status in the title
+    let error = match app.view_mode {
+        ViewMode::Search => app.pattern_error.as_ref().or(app.search_error.as_ref()),
+        _ => app.pattern_error.as_ref(),
+    };
+    let title = match error {
+        Some(error) => Span::styled(error.clone(), Style::default().fg(Color::Red)),
+        None => Span::raw(title),
     };
 
     let list = List::new(items)
@@ -881,26 +2651,65 @@ fn render_results_list(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     f.render_stateful_widget(list, area, &mut app.list_state.clone());
 }
 
-fn create_list_item(dir: &DirectoryEntry) -> ListItem {
-    if dir.count > 0 {
-        let content = Line::from(vec![
-            Span::raw(&dir.path),
-            Span::styled(
-                format!(" [{}]", dir.count),
+fn create_list_item(dir: &DirectoryEntry, show_sources: bool) -> ListItem<'_> {
+    let mut spans = Vec::new();
+    if let Some(name) = &dir.bookmark {
+        spans.push(Span::styled(
+            format!("@{name} "),
+            Style::default()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    // Highlight the characters the search input matched
+    let highlight = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::UNDERLINED);
+    let mut positions = dir.positions.iter().peekable();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in dir.path.chars().enumerate() {
+        let matched = positions.next_if_eq(&&i).is_some();
+        if matched != run_matched && !run.is_empty() {
+            let style = if run_matched {
+                highlight
+            } else {
                 Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            ),
-        ]);
-        ListItem::new(content)
+            };
+            spans.push(Span::styled(std::mem::take(&mut run), style));
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    let style = if run_matched {
+        highlight
     } else {
-        ListItem::new(Line::from(Span::raw(&dir.path)))
+        Style::default()
+    };
+    spans.push(Span::styled(run, style));
+
+    if dir.count > 0 {
+        spans.push(Span::styled(
+            format!(" [{}]", dir.count),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+
+    if show_sources && !dir.sources.is_empty() {
+        spans.push(Span::styled(
+            format!(" ({})", dir.sources.join(", ")),
+            Style::default().fg(Color::DarkGray),
+        ));
     }
+
+    ListItem::new(Line::from(spans))
 }
 
 fn render_help_text(f: &mut Frame, area: ratatui::layout::Rect) {
     let help = Paragraph::new(
-        "↑/↓: Navigate | Home/End: First/Last | Shift+Del: Reset Count | Enter: Select | Esc: Quit",
+        "↑/↓: Navigate | Home/End: First/Last | Shift+Del: Reset Count | Ctrl+T: Case | Enter: Select | Esc: Quit",
     )
     .style(Style::default().fg(Color::Gray))
     .block(Block::default().borders(Borders::ALL).title("Help"));
@@ -913,32 +2722,256 @@ fn print_help() {
 
     // Check if locate is available and show warning if not
     if !check_locate_available() {
-        print_locate_unavailable_message("WARNING");
+        println!("NOTE: The 'locate' command is not available on this system.");
+        println!("Directories will be found by walking the filesystem instead, which is slower.");
+        println!("For faster search, install the 'plocate' package and run 'sudo updatedb'.");
         println!();
     }
 
     println!("USAGE:");
     println!("    ccd-pick -i                   Enter interactive mode");
-    println!("    ccd-pick -b                   Bookmark current directory");
-    println!("    ccd-pick --install            Install ccd shell function to ~/.bashrc");
-    println!("    ccd-pick --printfn            Print shell function to standard output");
-    println!("    ccd-pick <search_pattern>     Search for directories matching pattern");
+    println!("    ccd-pick -b [name]            Bookmark current directory as @name");
+    println!("    ccd-pick @name                Go to a bookmarked directory");
+    println!("    ccd-pick --bookmarks          List bookmarks");
+    println!("    ccd-pick --remove-bookmark <name>");
+    println!("                                  Delete a bookmark");
+    println!("    ccd-pick --install [bash|zsh|fish] [--track]");
+    println!("                                  Install ccd shell function into the shell startup");
+    println!("    ccd-pick --uninstall [bash|zsh|fish]");
+    println!("                                  Remove the installed ccd shell function");
+    println!("    ccd-pick --printfn [bash|zsh|fish] [--track]");
+    println!("                                  Print shell function to standard output");
+    println!("    ccd-pick init <bash|zsh|fish|nushell> [--cmd NAME] [--hook prompt|none]");
+    println!(
+        "                                  Print the shell function named NAME (default: ccd)"
+    );
+    println!("    ccd-pick --track <path>       Record a directory change (used by the hook)");
+    println!("    ccd-pick <search_pattern>...  Search for directories matching all terms");
+    println!("    ccd-pick -- <search_pattern>...");
+    println!("                                  Search, even for words like list or init");
+    println!("    ccd-pick --regex <regex>      Search with a regular expression (re:<regex>)");
+    println!(
+        "    ccd-pick --glob <glob>        Search with a glob over path components (glob:<glob>)"
+    );
+    println!("    ccd-pick --import <tool> [file]");
+    println!("                                  Import history from zoxide, z, autojump or fasd");
+    println!("    ccd-pick --export [--format json|csv|tsv]");
+    println!("                                  Print the frequency database (default: json)");
+    println!("    ccd-pick --import-file <file|-> [--format json|csv|tsv] [--merge|--replace]");
+    println!("                                  Load an export, adding counts (--merge, default)");
+    println!("                                  or replacing the whole database (--replace)");
+    println!("    ccd-pick --merge-files <file>... [--strategy sum|max] [--rewrite FROM=TO]...");
+    println!("                                  Merge frequency files from other machines");
+    println!("    ccd-pick list [--sort frecency|count|recent|path] [--format plain|tsv|json]");
+    println!("                                  List recorded directories");
+    println!("    ccd-pick add <path> [--weight N]");
+    println!("                                  Record N visits to a directory (default: 1)");
+    println!("    ccd-pick set <path> <count>   Set the count of a directory (0 removes it)");
+    println!("    ccd-pick remove <path|pattern> [--dry-run]");
+    println!("                                  Forget a directory, or all containing a pattern");
+    println!("    ccd-pick --increment <path>   Record one visit (same as add <path>)");
+    println!("    ccd-pick --clean [--dry-run] [--grace DAYS]");
+    println!("                                  Forget directories that no longer exist");
     println!();
     println!("DESCRIPTION:");
-    println!("    Uses the locate database to quickly look up directories to cd into.");
+    println!("    Uses the locate database to quickly look up directories to cd into,");
+    println!("    or walks the filesystem when locate is unavailable.");
     println!("    Remembers most frequently used directories for faster access.");
     println!("    Invoked via the ccd wrapper function: install it, then run ccd.");
     println!();
     println!("OPTIONS:");
     println!("    -h, --help       Show this help message");
     println!("    -i               Interactive mode (used internally by shell wrapper)");
-    println!("    -b, --bookmark   Add current directory to bookmarks with frequency 1");
-    println!("    --install        Install shell function to ~/.bashrc (creates backup)");
+    println!("    -b, --bookmark   Bookmark current directory by name (default: directory name)");
+    println!("    --install        Install shell function to ~/.bashrc, ~/.zshrc or fish's conf.d");
+    println!("                     (creates backup); the shell is detected unless named");
+    println!("    --uninstall      Remove the shell function added by --install (creates backup)");
     println!("    --printfn        Print the shell function to standard output");
+    println!("    --track          With --install or --printfn: record every directory change");
+    println!("    --cmd NAME       With init: name of the shell function (default: ccd)");
+    println!("    --hook MODE      With init: prompt records every directory change, none doesn't");
+    println!();
+    println!("QUERIES:");
+    println!("    Space-separated terms must all match; each matches fuzzily.");
+    println!("    !term            Exclude paths matching term");
+    println!("    ^term, term$     Paths starting or ending with term");
+    println!("    term/            Match term against the last path component only");
+    println!("    a/b/c            Path components starting with a, b and c, in order");
+    println!("    re:<regex>       A regular expression searched for in the path");
+    println!(
+        "    glob:<glob>      A glob matching the last components, or all if it starts with /"
+    );
+    println!();
+    println!("ENVIRONMENT:");
+    println!(
+        "    CCD_BACKENDS              Ordered search backends (default: locate,walk?,frequency)"
+    );
+    println!("    CCD_BACKEND               Used when CCD_BACKENDS is unset: auto, locate or walk");
+    println!("    CCD_RESULT_LIMIT          Directories per backend, loaded again on scroll (default: 100)");
+    println!(
+        "    CCD_LOCATE_DATABASE       plocate/mlocate database to read instead of the default"
+    );
+    println!(
+        "    CCD_SEARCH_COMMAND        Shell command for the 'command' backend ($1 is the pattern)"
+    );
+    println!(
+        "    CCD_WALK_ROOTS            Colon-separated roots for walk/fd/find (default: $HOME)"
+    );
+    println!("    CCD_WALK_DEPTH            Maximum walk depth (default: 12)");
+    println!("    CCD_WALK_IGNORE           Colon-separated names or absolute paths to skip");
+    println!(
+        "    CCD_LOCATE_MAX_AGE_DAYS   Also walk when the locate database is older (default: 7)"
+    );
+    println!(
+        "    CCD_AUTO_CLEAN            Forget missing directories on every save (default: false)"
+    );
+    println!("    CCD_CLEAN_GRACE_DAYS      Keep missing directories this long (default: 30)");
+    println!("    CCD_TRACK_WEIGHT          Count per directory change recorded by the hook (default: 1)");
+    println!(
+        "    CCD_EXCLUDE               Space-separated globs (or re:regex) never shown or recorded"
+    );
+    println!("    CCD_EXCLUDE_DEFAULTS      Also use the built-in exclusions (default: true)");
+    println!("    CCD_CASE                  smart (ignore case unless the query has uppercase),");
+    println!("                              sensitive or insensitive (default: smart)");
+    println!("    CCD_DATA_DIR              Data directory (default: $XDG_DATA_HOME/ccd)");
+    println!("    CCD_CONFIG_DIR            Config directory (default: $XDG_CONFIG_HOME/ccd)");
+    println!();
+    println!("FILES:");
+    println!("    ~/.local/share/ccd/frequency  Usage counts and last access times");
+    println!("    ~/.config/ccd/config          Settings as 'key = value' lines, using the");
+    println!("                                  names above without CCD_ (e.g. result_limit)");
     println!();
     println!("INTERACTIVE MODE:");
     println!("    Type to search, use ↑/↓ to navigate, PgUp/PgDn for fast navigation");
-    println!("    Home/End to jump to first/last, Tab to toggle frequent/search view");
-    println!("    Shift+Del to reset frequency count, Enter to select, Esc to quit");
-    println!("    Directories are sorted by usage frequency (most used first)");
+    println!("    Home/End to jump to first/last, Tab to cycle search/frequent/bookmarks views");
+    println!("    Shift+Del to reset frequency count (bookmarks are kept), Ctrl+T to switch");
+    println!("    between smart case, case sensitive and ignoring case, Enter to select,");
+    println!("    Esc to quit");
+    println!("    Patterns match fuzzily: their characters must appear in order (ccdpk finds");
+    println!("    ccd-pick), and matched characters are highlighted");
+    println!("    Directories are sorted by match score and frecency (frequency weighted by");
+    println!("    recency)");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AWKWARD: &[&str] = &[
+        "/home/me/plain",
+        "/home/me/tab\there",
+        "/home/me/new\nline",
+        "/home/me/carriage\rreturn",
+        "/home/me/back\\slash",
+        "/home/me/trailing\\",
+        "/home/me/a=b",
+        "/home/me/\\t is not a tab",
+        "/home/jürgen/dåta",
+    ];
+
+    // Write `frequency_map` as our own file would be, and read it back
+    fn round_trip(frequency_map: &FrequencyMap) -> (FrequencyMap, u32, Vec<usize>) {
+        let mut file = Vec::new();
+        FrequencyManager::write_entries(&mut file, frequency_map).unwrap();
+        let content = String::from_utf8(file).unwrap();
+        FrequencyManager::parse_file(Path::new("frequency"), &content).unwrap()
+    }
+
+    #[test]
+    fn escaped_fields_round_trip() {
+        for value in AWKWARD {
+            let escaped = escape_field(value);
+            assert!(!escaped.contains(['\t', '\n', '\r']), "{escaped:?}");
+            assert_eq!(unescape_field(&escaped).as_deref(), Some(*value));
+        }
+        assert_eq!(unescape_field(r"a\=b").as_deref(), Some("a=b"));
+        assert_eq!(unescape_field(r"bad\q"), None);
+        assert_eq!(unescape_field("trailing\\"), None);
+    }
+
+    #[test]
+    fn metadata_fields_split_on_the_first_unescaped_equals() {
+        assert_eq!(split_metadata_field("tag=work"), Some(("tag", "work")));
+        assert_eq!(split_metadata_field("note=a=b"), Some(("note", "a=b")));
+        assert_eq!(split_metadata_field(r"a\=b=c"), Some((r"a\=b", "c")));
+        assert_eq!(split_metadata_field(r"a\\=b"), Some((r"a\\", "b")));
+        assert_eq!(split_metadata_field("no value"), None);
+        assert_eq!(split_metadata_field(r"only\=escaped"), None);
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let mut frequency_map = FrequencyMap::new();
+        for (i, path) in AWKWARD.iter().enumerate() {
+            let mut entry = FrequencyEntry::new(i as u32 + 1, 1_700_000_000 + i as u64);
+            for key in AWKWARD {
+                entry.metadata.insert(key.to_string(), path.to_string());
+            }
+            frequency_map.insert(path.to_string(), entry);
+        }
+
+        let (parsed, version, malformed) = round_trip(&frequency_map);
+        assert_eq!(version, FREQUENCY_FILE_VERSION);
+        assert!(malformed.is_empty(), "{malformed:?}");
+        assert_eq!(parsed, frequency_map);
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        assert!(FrequencyManager::parse_line("3\t100\t/home/me\ttag=work").is_some());
+        assert!(FrequencyManager::parse_line("3\t100\t").is_none());
+        assert!(FrequencyManager::parse_line("x\t100\t/home/me").is_none());
+        assert!(FrequencyManager::parse_line("3\t100\t/bad\\q").is_none());
+        assert!(FrequencyManager::parse_line("3\t100\t/home/me\tno value").is_none());
+
+        let content = "# ccd frequency v2\n3\t100\t/home/me\nnot a line\n\n# comment\n";
+        let (frequency_map, _, malformed) =
+            FrequencyManager::parse_file(Path::new("frequency"), content).unwrap();
+        assert_eq!(frequency_map.len(), 1);
+        assert_eq!(malformed, vec![3]);
+    }
+
+    #[test]
+    fn legacy_lines() {
+        let (path, entry) = FrequencyManager::parse_legacy_line("3\t/home/me").unwrap();
+        assert_eq!(
+            (path.as_str(), entry.count, entry.last_access),
+            ("/home/me", 3, 0)
+        );
+        let (path, entry) = FrequencyManager::parse_legacy_line("3\t100\t/home/me").unwrap();
+        assert_eq!((path.as_str(), entry.last_access), ("/home/me", 100));
+        // Paths weren't escaped, so a tab after a path-like field belongs to the path
+        let (path, _) = FrequencyManager::parse_legacy_line("3\t/home/a\tb").unwrap();
+        assert_eq!(path, "/home/a\tb");
+        let (path, _) = FrequencyManager::parse_legacy_line("3\t/home/back\\slash").unwrap();
+        assert_eq!(path, r"/home/back\slash");
+        assert!(FrequencyManager::parse_legacy_line("3\t").is_none());
+        assert!(FrequencyManager::parse_legacy_line("/home/me").is_none());
+    }
+
+    #[test]
+    fn version_1_files_are_migrated() {
+        let content = "5\t/home/me\n2\t1700000000\t/srv/www\n1\t/home/back\\slash\n";
+        let (frequency_map, version, malformed) =
+            FrequencyManager::parse_file(Path::new("frequency"), content).unwrap();
+        assert_eq!(version, 1);
+        assert!(malformed.is_empty());
+        assert_eq!(frequency_map.len(), 3);
+        assert_eq!(frequency_map["/srv/www"].last_access, 1_700_000_000);
+        assert!(frequency_map.contains_key(r"/home/back\slash"));
+
+        let (migrated, version, malformed) = round_trip(&frequency_map);
+        assert_eq!(version, FREQUENCY_FILE_VERSION);
+        assert!(malformed.is_empty());
+        assert_eq!(migrated, frequency_map);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let content = format!("{FREQUENCY_FILE_HEADER}{}\n", FREQUENCY_FILE_VERSION + 1);
+        assert!(matches!(
+            FrequencyManager::parse_file(Path::new("frequency"), &content),
+            Err(CddError::FrequencyFile(_))
+        ));
+    }
 }
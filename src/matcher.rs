@@ -0,0 +1,1136 @@
+// Matching of search queries against directory paths. A query is a list of space-separated
+// terms that must all match:
+//
+//   term    the term's characters appear in the path in order (fuzzy, in the style of fzf)
+//   !term   paths containing the term are excluded
+//   ^term   the path starts with the term, and term$ ends with it (exact, not fuzzy)
+//   term/   only the last path component is matched; ^ and $ then anchor to it
+//   a/b/c   path components starting with a, b and c, in order but not necessarily adjacent
+//           (`w/cc/src` finds ~/work/ccd/src); a trailing '/' makes c the last component
+//
+// Matches are scored so that consecutive characters, word boundaries and the last path
+// component rank higher.
+//
+// A query starting with `re:` is instead a regular expression searched for in the path, and
+// one starting with `glob:` a glob matched against whole path components (see `Glob`).
+//
+// Whether case matters is decided by a `CaseMode` for the whole query.
+use regex::{Regex, RegexBuilder};
+
+use crate::exclude::match_component;
+use crate::CddError;
+
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+// Bonuses for matching right after a '/', after another non-word character (`-`, `_`, `.`,
+// ...) and at a camelCase or letter-to-digit transition
+const BONUS_SEPARATOR: i32 = 9;
+const BONUS_BOUNDARY: i32 = 8;
+const BONUS_CAMEL: i32 = 7;
+// Minimum bonus for every character continuing a run of matches
+const BONUS_CONSECUTIVE: i32 = 4;
+// Added for every matched character in the last path component
+const BONUS_BASENAME: i32 = 4;
+// The bonus of the first pattern character counts this many times
+const FIRST_CHAR_MULTIPLIER: i32 = 2;
+// Marks table cells where the pattern character doesn't match
+const UNMATCHED: i32 = i32::MIN;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Match {
+    pub score: i32,
+    // Indices of the matched characters in the path, for highlighting
+    pub positions: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Separator,
+    NonWord,
+    Lower,
+    Upper,
+    Digit,
+    // Letters without case, e.g. CJK
+    Letter,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c == '/' {
+        CharClass::Separator
+    } else if c.is_lowercase() {
+        CharClass::Lower
+    } else if c.is_uppercase() {
+        CharClass::Upper
+    } else if c.is_numeric() {
+        CharClass::Digit
+    } else if c.is_alphabetic() {
+        CharClass::Letter
+    } else {
+        CharClass::NonWord
+    }
+}
+
+// Bonus for matching a character of class `class` that follows one of class `previous`
+fn bonus(previous: CharClass, class: CharClass) -> i32 {
+    match (previous, class) {
+        (_, CharClass::Separator) | (CharClass::Separator, _) => BONUS_SEPARATOR,
+        (_, CharClass::NonWord) | (CharClass::NonWord, _) => BONUS_BOUNDARY,
+        (CharClass::Lower, CharClass::Upper)
+        | (CharClass::Lower | CharClass::Upper | CharClass::Letter, CharClass::Digit) => {
+            BONUS_CAMEL
+        }
+        _ => 0,
+    }
+}
+
+// Whether letter case matters when matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    // Ignore case unless the query contains an uppercase letter
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+impl CaseMode {
+    pub fn parse(name: &str) -> Result<Self, CddError> {
+        match name {
+            "smart" => Ok(CaseMode::Smart),
+            "sensitive" => Ok(CaseMode::Sensitive),
+            "insensitive" => Ok(CaseMode::Insensitive),
+            _ => Err(CddError::Config(format!(
+                "unknown case mode '{name}' (expected smart, sensitive or insensitive)"
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CaseMode::Smart => "smart case",
+            CaseMode::Sensitive => "case sensitive",
+            CaseMode::Insensitive => "ignoring case",
+        }
+    }
+
+    // The mode after this one, for toggling through them
+    pub fn next(self) -> Self {
+        match self {
+            CaseMode::Smart => CaseMode::Sensitive,
+            CaseMode::Sensitive => CaseMode::Insensitive,
+            CaseMode::Insensitive => CaseMode::Smart,
+        }
+    }
+
+    // Whether to ignore case for `pattern`. In a regular expression, escaped letters such as
+    // `\W` are character classes rather than uppercase letters.
+    fn ignore_case(self, pattern: &str, regex: bool) -> bool {
+        match self {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => {
+                let mut escaped = false;
+                !pattern.chars().any(|c| {
+                    let uppercase = c.is_uppercase() && !escaped;
+                    escaped = regex && c == '\\' && !escaped;
+                    uppercase
+                })
+            }
+        }
+    }
+}
+
+// Characters are compared after folding, which lowercases them when ignoring case
+fn fold(c: char, ignore_case: bool) -> char {
+    if !ignore_case {
+        c
+    } else if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn starts_with(
+    mut text: impl Iterator<Item = char>,
+    mut prefix: impl Iterator<Item = char>,
+) -> bool {
+    prefix.all(|p| text.next() == Some(p))
+}
+
+fn escape_glob(c: char, glob: &mut String) {
+    if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+        glob.push('\\');
+    }
+    glob.push(c);
+}
+
+// A path prepared for scoring
+struct Candidate {
+    folded: Vec<char>,
+    // Bonus for matching at each position
+    boundaries: Vec<i32>,
+    basename_start: usize,
+}
+
+impl Candidate {
+    fn new(path: &str, ignore_case: bool) -> Self {
+        let mut previous = CharClass::Separator;
+        let boundaries = path
+            .chars()
+            .map(|c| {
+                let class = char_class(c);
+                let char_bonus = bonus(previous, class);
+                previous = class;
+                char_bonus
+            })
+            .collect();
+        let folded: Vec<char> = path.chars().map(|c| fold(c, ignore_case)).collect();
+        let basename_start = folded.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+        Self {
+            folded,
+            boundaries,
+            basename_start,
+        }
+    }
+
+    fn basename_bonus(&self, j: usize) -> i32 {
+        if j >= self.basename_start {
+            BONUS_BASENAME
+        } else {
+            0
+        }
+    }
+
+    // Score matching pattern characters at the given positions
+    fn alignment_score(&self, positions: &[usize]) -> i32 {
+        self.continued_score(None, positions)
+    }
+
+    // Score matching pattern characters at `positions` after the previous one was matched at
+    // column `previous` (None for the first pattern character)
+    fn continued_score(&self, mut previous: Option<usize>, positions: &[usize]) -> i32 {
+        let mut score = 0;
+        let mut run_bonus = 0;
+        for &j in positions {
+            let char_bonus = self.boundaries[j];
+            score += SCORE_MATCH + self.basename_bonus(j);
+            match previous {
+                None => {
+                    score += char_bonus * FIRST_CHAR_MULTIPLIER;
+                    run_bonus = char_bonus;
+                }
+                Some(previous) if previous + 1 == j => {
+                    // A run keeps the bonus of the boundary it started at
+                    if char_bonus >= BONUS_BOUNDARY && char_bonus > run_bonus {
+                        run_bonus = char_bonus;
+                    }
+                    score += char_bonus.max(run_bonus).max(BONUS_CONSECUTIVE);
+                }
+                Some(previous) => {
+                    let gap = (j - previous - 1) as i32;
+                    score += SCORE_GAP_START + (gap - 1) * SCORE_GAP_EXTENSION + char_bonus;
+                    run_bonus = char_bonus;
+                }
+            }
+            previous = Some(j);
+        }
+        score
+    }
+
+    // Columns where the path components start
+    fn component_starts(&self) -> Vec<usize> {
+        (0..self.folded.len())
+            .filter(|&j| self.folded[j] != '/' && (j == 0 || self.folded[j - 1] == '/'))
+            .collect()
+    }
+
+    // Find the best-scoring way to match each segment as the prefix of a path component, in
+    // order. Segments are separated by at least a '/', so each starts a new run and its score
+    // only depends on where the previous one ended: the best choice for every segment and
+    // component follows from the best choices for the previous segment. With `basename`, the
+    // last segment must match the last component.
+    fn segment_match(&self, segments: &[Vec<char>], basename: bool) -> Option<Match> {
+        let starts = self.component_starts();
+        let fits = |segment: &[char], start: usize| self.folded[start..].starts_with(segment);
+        let positions = |segment: &[char], start: usize| start..start + segment.len();
+
+        // Per segment and component: the best score, and the component the previous segment
+        // was matched at
+        let mut best: Vec<Vec<Option<(i32, usize)>>> = Vec::with_capacity(segments.len());
+        for (i, segment) in segments.iter().enumerate() {
+            let row = starts
+                .iter()
+                .enumerate()
+                .map(|(k, &start)| {
+                    if !fits(segment, start) {
+                        return None;
+                    }
+                    let here: Vec<usize> = positions(segment, start).collect();
+                    if i == 0 {
+                        return Some((self.alignment_score(&here), 0));
+                    }
+                    (0..k)
+                        .filter_map(|previous| {
+                            let (score, _) = best[i - 1][previous]?;
+                            let end = starts[previous] + segments[i - 1].len() - 1;
+                            Some((score + self.continued_score(Some(end), &here), previous))
+                        })
+                        .rev()
+                        .max_by_key(|&(score, _)| score)
+                })
+                .collect();
+            best.push(row);
+        }
+
+        let last = best.last()?;
+        let mut end = if basename {
+            let k = starts.len().checked_sub(1)?;
+            last[k]?;
+            k
+        } else {
+            (0..starts.len())
+                .filter(|&k| last[k].is_some())
+                .rev()
+                .max_by_key(|&k| last[k].map(|(score, _)| score))?
+        };
+        let score = last[end]?.0;
+
+        let mut matched = Vec::new();
+        for i in (0..segments.len()).rev() {
+            matched.extend(positions(&segments[i], starts[end]).rev());
+            end = best[i][end]?.1;
+        }
+        matched.reverse();
+        Some(Match {
+            score,
+            positions: matched,
+        })
+    }
+
+    // Find the best-scoring way to match `pattern` fuzzily at or after column `first`. Like
+    // fzf, this fills a table of the best score for each pattern character matched at each
+    // position (scored as in `alignment_score`), then walks it back from the best final cell.
+    fn fuzzy_match(&self, pattern: &[char], first: usize) -> Option<Match> {
+        let (rows, columns) = (pattern.len(), self.folded.len());
+
+        // Per cell: the score, the bonus of the run of consecutive matches it ends, and the
+        // column the previous pattern character was matched at
+        let mut scores = vec![UNMATCHED; rows * columns];
+        let mut run_bonuses = vec![0; rows * columns];
+        let mut from = vec![0; rows * columns];
+
+        for (i, &p) in pattern.iter().enumerate() {
+            // Best score of the previous row with a gap before column j, and where it came from
+            let mut gap: Option<(i32, usize)> = None;
+            for j in first..columns {
+                let cell = i * columns + j;
+                if i > 0 && j >= first + 2 {
+                    let extended = gap.map(|(score, k)| (score + SCORE_GAP_EXTENSION, k));
+                    let left = scores[cell - columns - 2];
+                    if left != UNMATCHED
+                        && extended.is_none_or(|(score, _)| left + SCORE_GAP_START >= score)
+                    {
+                        gap = Some((left + SCORE_GAP_START, j - 2));
+                    } else {
+                        gap = extended;
+                    }
+                }
+                if self.folded[j] != p {
+                    continue;
+                }
+
+                let char_bonus = self.boundaries[j];
+                let basename = self.basename_bonus(j);
+                if i == 0 {
+                    scores[cell] = SCORE_MATCH + char_bonus * FIRST_CHAR_MULTIPLIER + basename;
+                    run_bonuses[cell] = char_bonus;
+                    continue;
+                }
+
+                // Continue a run of consecutive matches
+                let mut best = (UNMATCHED, 0, 0);
+                if j > first && scores[cell - columns - 1] != UNMATCHED {
+                    let mut run_bonus = run_bonuses[cell - columns - 1];
+                    if char_bonus >= BONUS_BOUNDARY && char_bonus > run_bonus {
+                        run_bonus = char_bonus;
+                    }
+                    let consecutive = char_bonus.max(run_bonus).max(BONUS_CONSECUTIVE);
+                    best = (
+                        scores[cell - columns - 1] + SCORE_MATCH + consecutive + basename,
+                        run_bonus,
+                        j - 1,
+                    );
+                }
+                // Or start a new run after a gap
+                if let Some((gap_score, k)) = gap {
+                    let after_gap = gap_score + SCORE_MATCH + char_bonus + basename;
+                    if after_gap > best.0 {
+                        best = (after_gap, char_bonus, k);
+                    }
+                }
+                (scores[cell], run_bonuses[cell], from[cell]) = best;
+            }
+        }
+
+        // The basename bonus already favors later matches, so take the first of equally good
+        // ones, which keeps runs together
+        let last_row = (rows - 1) * columns;
+        let end = (first..columns)
+            .filter(|&j| scores[last_row + j] != UNMATCHED)
+            .rev()
+            .max_by_key(|&j| scores[last_row + j])?;
+
+        let mut positions = vec![end; rows];
+        for i in (1..rows).rev() {
+            positions[i - 1] = from[i * columns + positions[i]];
+        }
+        Some(Match {
+            score: scores[last_row + end],
+            positions,
+        })
+    }
+}
+
+// One space-separated word of a query
+#[derive(Debug, Clone, Default)]
+struct Term {
+    chars: Vec<char>,
+    negated: bool,
+    anchored_start: bool,
+    anchored_end: bool,
+    basename: bool,
+    // The parts of a fuzzy term with an interior '/', matched against path components
+    segments: Vec<Vec<char>>,
+    ignore_case: bool,
+}
+
+impl Term {
+    fn parse(word: &str, ignore_case: bool) -> Self {
+        // A lone `!` or `/` is matched literally
+        let (negated, word) = match word.strip_prefix('!') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, word),
+        };
+        let (basename, word) = match word.strip_suffix('/') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, word),
+        };
+        let (anchored_start, word) = match word.strip_prefix('^') {
+            Some(rest) => (true, rest),
+            None => (false, word),
+        };
+        let (anchored_end, word) = match word.strip_suffix('$') {
+            Some(rest) => (true, rest),
+            None => (false, word),
+        };
+        let mut term = Self {
+            chars: word.chars().map(|c| fold(c, ignore_case)).collect(),
+            negated,
+            anchored_start,
+            anchored_end,
+            basename,
+            segments: Vec::new(),
+            ignore_case,
+        };
+        if !term.is_exact() {
+            let segments: Vec<Vec<char>> = word
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(|segment| segment.chars().map(|c| fold(c, ignore_case)).collect())
+                .collect();
+            if segments.len() > 1 {
+                term.segments = segments;
+            }
+        }
+        term
+    }
+
+    // Negated and anchored terms must appear literally
+    fn is_exact(&self) -> bool {
+        self.negated || self.anchored_start || self.anchored_end
+    }
+
+    fn chars(&self) -> impl DoubleEndedIterator<Item = char> + '_ {
+        self.chars.iter().copied()
+    }
+
+    fn fold<'a>(&self, text: &'a str) -> impl DoubleEndedIterator<Item = char> + 'a {
+        let ignore_case = self.ignore_case;
+        text.chars().map(move |c| fold(c, ignore_case))
+    }
+
+    // Whether the term occurs in `path`, ignoring negation
+    fn occurs_in(&self, path: &str) -> bool {
+        if !self.segments.is_empty() {
+            return self.segments_occur_in(path);
+        }
+        let text = if self.basename { basename(path) } else { path };
+        let folded = || self.fold(text);
+        match (self.anchored_start, self.anchored_end) {
+            (true, true) => folded().eq(self.chars()),
+            (true, false) => starts_with(folded(), self.chars()),
+            (false, true) => starts_with(folded().rev(), self.chars().rev()),
+            (false, false) if self.is_exact() => {
+                self.chars.is_empty()
+                    || text
+                        .char_indices()
+                        .any(|(i, _)| starts_with(self.fold(&text[i..]), self.chars()))
+            }
+            (false, false) => {
+                let mut pattern = self.chars().peekable();
+                for c in folded() {
+                    if pattern.peek() == Some(&c) {
+                        pattern.next();
+                    }
+                }
+                pattern.peek().is_none()
+            }
+        }
+    }
+
+    // Whether the segments are prefixes of path components in order. Taking the first
+    // component each segment fits leaves the most room for the following ones.
+    fn segments_occur_in(&self, path: &str) -> bool {
+        let fits = |segment: &[char], component: &str| {
+            starts_with(self.fold(component), segment.iter().copied())
+        };
+        let mut components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let mut segments = self.segments.as_slice();
+        if self.basename {
+            let (Some(last_segment), Some(last_component)) = (segments.last(), components.pop())
+            else {
+                return false;
+            };
+            if !fits(last_segment, last_component) {
+                return false;
+            }
+            segments = &segments[..segments.len() - 1];
+        }
+        let mut components = components.into_iter();
+        segments
+            .iter()
+            .all(|segment| components.any(|component| fits(segment, component)))
+    }
+
+    // Where a term known to occur in the candidate matches it best
+    fn find(&self, candidate: &Candidate) -> Option<Match> {
+        if self.chars.is_empty() {
+            return Some(Match::default());
+        }
+        if !self.segments.is_empty() {
+            return candidate.segment_match(&self.segments, self.basename);
+        }
+        let first = if self.basename {
+            candidate.basename_start
+        } else {
+            0
+        };
+        if !self.is_exact() {
+            return candidate.fuzzy_match(&self.chars, first);
+        }
+
+        // An anchored term can only match in one place
+        let start = if self.anchored_start {
+            first
+        } else {
+            candidate.folded.len().checked_sub(self.chars.len())?
+        };
+        let positions: Vec<usize> = (start..start + self.chars.len()).collect();
+        Some(Match {
+            score: candidate.alignment_score(&positions),
+            positions,
+        })
+    }
+
+    // A regular expression matching (a superset of) the paths the term occurs in
+    fn to_regex(&self) -> String {
+        if !self.segments.is_empty() {
+            let escaped: Vec<String> = self
+                .segments
+                .iter()
+                .map(|segment| regex::escape(&segment.iter().collect::<String>()))
+                .collect();
+            let end = if self.basename { "[^/]*$" } else { "" };
+            return format!("/{}{end}", escaped.join("[^/]*/(.*/)?"));
+        }
+        let escaped: Vec<String> = self
+            .chars()
+            .map(|c| regex::escape(&c.to_string()))
+            .collect();
+        // Within the basename, gaps can't cross a '/'
+        let body = match (self.is_exact(), self.basename) {
+            (true, _) => escaped.concat(),
+            (false, true) => escaped.join("[^/]*"),
+            (false, false) => escaped.join(".*"),
+        };
+        let start = match (self.anchored_start, self.basename) {
+            (true, true) => "/",
+            (true, false) => "^",
+            (false, _) => "",
+        };
+        let end = match (self.anchored_end, self.basename) {
+            (true, _) => "$",
+            (false, true) => "[^/]*$",
+            (false, false) => "",
+        };
+        format!("{start}{body}{end}")
+    }
+
+    // A glob matching (a superset of) the full paths the term occurs in
+    fn to_glob(&self) -> String {
+        let mut glob = String::new();
+        if !self.segments.is_empty() {
+            for segment in &self.segments {
+                glob.push_str("*/");
+                segment.iter().for_each(|&c| escape_glob(c, &mut glob));
+            }
+            glob.push('*');
+            return glob;
+        }
+        match (self.anchored_start, self.basename) {
+            (true, true) => glob.push_str("*/"),
+            (true, false) => {}
+            (false, _) => glob.push('*'),
+        }
+        for (i, c) in self.chars().enumerate() {
+            if i > 0 && !self.is_exact() {
+                glob.push('*');
+            }
+            escape_glob(c, &mut glob);
+        }
+        if !self.anchored_end {
+            glob.push('*');
+        }
+        glob
+    }
+}
+
+// A glob over path components, with `*`, `?`, `[...]` and `**` for any number of components.
+// Globs starting with '/' must match the whole path; others its last components, so
+// `glob:proj*/src` finds ~/projects/src but not ~/projects/src/bin.
+#[derive(Debug, Clone)]
+struct Glob {
+    components: Vec<Vec<char>>,
+    anchored: bool,
+    ignore_case: bool,
+}
+
+impl Glob {
+    fn new(pattern: &str, ignore_case: bool) -> Self {
+        Self {
+            components: Self::split(pattern, ignore_case),
+            anchored: pattern.starts_with('/'),
+            ignore_case,
+        }
+    }
+
+    fn split(path: &str, ignore_case: bool) -> Vec<Vec<char>> {
+        path.split('/')
+            .filter(|component| !component.is_empty())
+            .map(|component| component.chars().map(|c| fold(c, ignore_case)).collect())
+            .collect()
+    }
+
+    fn is_match(&self, path: &str) -> bool {
+        let folded = Self::split(path, self.ignore_case);
+        if self.anchored {
+            return matches_components(&self.components, &folded);
+        }
+        (0..=folded.len()).any(|start| matches_components(&self.components, &folded[start..]))
+    }
+
+    // A regular expression matching (a superset of) the paths the glob matches. Character
+    // classes become any character, which `is_match` sorts out afterwards.
+    fn to_regex(&self) -> String {
+        let mut regex = String::from(if self.anchored { "^/" } else { "/" });
+        let mut previous_any = false;
+        for (i, component) in self.components.iter().enumerate() {
+            let any = component.iter().collect::<String>() == "**";
+            if i > 0 {
+                // `**` may match no components at all, taking a separator with it
+                regex.push_str(if any || previous_any { "/?" } else { "/" });
+            }
+            previous_any = any;
+            if any {
+                regex.push_str(".*");
+                continue;
+            }
+            let mut chars = component.iter();
+            while let Some(&c) = chars.next() {
+                match c {
+                    '*' => regex.push_str("[^/]*"),
+                    '?' => regex.push_str("[^/]"),
+                    '[' if component.contains(&']') => {
+                        regex.push_str("[^/]");
+                        chars.by_ref().skip(1).find(|&&c| c == ']');
+                    }
+                    '\\' => {
+                        if let Some(&escaped) = chars.next() {
+                            regex.push_str(&regex::escape(&escaped.to_string()));
+                        }
+                    }
+                    c => regex.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+        }
+        regex.push('$');
+        regex
+    }
+
+    // A glob for `find -path`, whose `*` also matches '/', so `**` and the separators around
+    // it become a single `*`
+    fn to_glob(&self) -> String {
+        let mut glob = String::from(if self.anchored { "/" } else { "*/" });
+        let mut previous_any = false;
+        for (i, component) in self.components.iter().enumerate() {
+            let component: String = component.iter().collect();
+            let any = component == "**";
+            if i > 0 && !any && !previous_any {
+                glob.push('/');
+            }
+            previous_any = any;
+            glob.push_str(if any { "*" } else { &component });
+        }
+        glob
+    }
+}
+
+// Whether the glob components match all of `path`
+fn matches_components(pattern: &[Vec<char>], path: &[Vec<char>]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first.iter().collect::<String>() == "**" => {
+            (0..=path.len()).any(|skip| matches_components(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((component, path_rest)) => {
+                match_component(first, component) && matches_components(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Query {
+    Terms(Vec<Term>),
+    Regex(Regex),
+    Glob(Glob),
+}
+
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    query: String,
+    kind: Query,
+    ignore_case: bool,
+}
+
+impl Matcher {
+    // Fails for an invalid `re:` regular expression
+    pub fn new(query: &str, case: CaseMode) -> Result<Self, CddError> {
+        let (kind, ignore_case) = if let Some(pattern) = query.strip_prefix("re:") {
+            let ignore_case = case.ignore_case(pattern, true);
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|e| CddError::InvalidPattern(regex_error(&e)))?;
+            (Query::Regex(regex), ignore_case)
+        } else if let Some(pattern) = query.strip_prefix("glob:") {
+            let ignore_case = case.ignore_case(pattern, false);
+            (Query::Glob(Glob::new(pattern, ignore_case)), ignore_case)
+        } else {
+            let ignore_case = case.ignore_case(query, false);
+            let terms = query
+                .split_whitespace()
+                .map(|word| Term::parse(word, ignore_case))
+                .collect();
+            (Query::Terms(terms), ignore_case)
+        };
+        Ok(Self {
+            query: query.to_string(),
+            kind,
+            ignore_case,
+        })
+    }
+
+    // Whether matching ignores case, which external tools must be told
+    pub fn ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    // The query as typed
+    pub fn pattern(&self) -> &str {
+        &self.query
+    }
+
+    // Whether `path` satisfies the query. Cheaper than `score`, for scanning many candidates.
+    pub fn is_match(&self, path: &str) -> bool {
+        match &self.kind {
+            Query::Terms(terms) => terms
+                .iter()
+                .all(|term| term.occurs_in(path) != term.negated),
+            Query::Regex(regex) => regex.is_match(path),
+            Query::Glob(glob) => glob.is_match(path),
+        }
+    }
+
+    // For terms, the sum of their best scores with all their matched positions. Regular
+    // expressions and globs don't score matches, leaving the order to frecency; the first
+    // match of a regular expression is highlighted.
+    pub fn score(&self, path: &str) -> Option<Match> {
+        let terms = match &self.kind {
+            Query::Terms(terms) => terms,
+            Query::Regex(regex) => {
+                let found = regex.find(path)?;
+                let positions = path
+                    .char_indices()
+                    .enumerate()
+                    .filter(|(_, (offset, _))| found.range().contains(offset))
+                    .map(|(i, _)| i)
+                    .collect();
+                return Some(Match {
+                    score: 0,
+                    positions,
+                });
+            }
+            Query::Glob(glob) => return glob.is_match(path).then(Match::default),
+        };
+        if !self.is_match(path) {
+            return None;
+        }
+        let candidate = Candidate::new(path, self.ignore_case);
+        let mut total = Match::default();
+        for term in terms.iter().filter(|term| !term.negated) {
+            let found = term.find(&candidate)?;
+            total.score += found.score;
+            total.positions.extend(found.positions);
+        }
+        total.positions.sort_unstable();
+        total.positions.dedup();
+        Some(total)
+    }
+
+    // External tools are given the term with the most characters, and their output is
+    // filtered with `is_match`
+    fn selective_term(terms: &[Term]) -> Option<&Term> {
+        terms
+            .iter()
+            .filter(|term| !term.negated)
+            .max_by_key(|term| term.chars.len())
+    }
+
+    // A regular expression for `locate --regex` and fd
+    pub fn to_regex(&self) -> String {
+        match &self.kind {
+            // Every absolute path contains a '/'
+            Query::Terms(terms) => {
+                Self::selective_term(terms).map_or_else(|| "/".to_string(), Term::to_regex)
+            }
+            Query::Regex(regex) => regex.as_str().to_string(),
+            Query::Glob(glob) => glob.to_regex(),
+        }
+    }
+
+    // A regular expression for `locate --regex`, which uses POSIX extended regular
+    // expressions. Regular expressions using Rust-only syntax (`\d`, `(?:...)`, lazy
+    // repetition, ...) would fail or match differently there, so locate is then asked for
+    // everything and its output left to `is_match`.
+    pub fn to_posix_regex(&self) -> String {
+        match &self.kind {
+            Query::Regex(regex) if !is_posix_compatible(regex.as_str()) => "/".to_string(),
+            _ => self.to_regex(),
+        }
+    }
+
+    // A glob for `find -path` or `-ipath`
+    pub fn to_glob(&self) -> String {
+        match &self.kind {
+            Query::Terms(terms) => {
+                Self::selective_term(terms).map_or_else(|| "*".to_string(), Term::to_glob)
+            }
+            // A regular expression can't be turned into a glob, so filter everything
+            Query::Regex(_) => "*".to_string(),
+            Query::Glob(glob) => glob.to_glob(),
+        }
+    }
+}
+
+// Whether a regular expression means the same as a POSIX extended regular expression, as
+// far as a quick look can tell: no escaped letters or digits (classes, anchors, back
+// references), no `(?` groups or flags, and no lazy repetition
+fn is_posix_compatible(pattern: &str) -> bool {
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let next = chars.peek().copied();
+        match (c, next) {
+            ('\\', Some(escaped)) if escaped.is_alphanumeric() => return false,
+            ('\\', _) => {
+                chars.next();
+            }
+            ('(', Some('?')) | ('*' | '+' | '?' | '}', Some('?')) => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+// The regex crate's messages span several lines, pointing at the error with a caret; keep the
+// last line, which says what is wrong
+fn regex_error(error: &regex::Error) -> String {
+    let message = error.to_string();
+    message
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map_or(message.clone(), |line| {
+            line.trim().trim_start_matches("error: ").to_string()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATHS: &[&str] = &[
+        "/home/me/src/ccd-pick",
+        "/home/me/src",
+        "/home/me/old/src",
+        "/home/me/website",
+        "/srv/web/website",
+        "/home/me/work/ccd/src",
+        "/home/me/work/other/ccd/src/bin",
+        "/home/me/Projects/website",
+        "/home/me/projects/src",
+        "/tmp/fz/src",
+        "/tmp/fz/a/b/src",
+        "/x/cache/src",
+        "/home/jürgen/Ünïcode-dåta",
+    ];
+
+    fn matcher(query: &str) -> Matcher {
+        Matcher::new(query, CaseMode::Smart).unwrap()
+    }
+
+    // The characters of `path` at the matched positions
+    fn highlighted(path: &str, found: &Match) -> String {
+        let chars: Vec<char> = path.chars().collect();
+        found.positions.iter().map(|&i| chars[i]).collect()
+    }
+
+    // `find -path` semantics, where `*` also matches '/'
+    fn find_glob_matches(pattern: &str, path: &str, ignore_case: bool) -> bool {
+        let pattern: Vec<char> = pattern.chars().map(|c| fold(c, ignore_case)).collect();
+        let text: Vec<char> = path.chars().map(|c| fold(c, ignore_case)).collect();
+        match_component(&pattern, &text)
+    }
+
+    // The regex and glob given to external tools must never reject a path `is_match` accepts
+    fn assert_external_patterns_cover(queries: &[&str]) {
+        for query in queries {
+            let m = matcher(query);
+            let regex = RegexBuilder::new(&m.to_regex())
+                .case_insensitive(m.ignore_case())
+                .build()
+                .unwrap();
+            assert!(
+                PATHS.iter().any(|path| m.is_match(path)),
+                "{query} matches nothing"
+            );
+            for path in PATHS.iter().filter(|path| m.is_match(path)) {
+                assert!(regex.is_match(path), "{query}: {} vs {path}", m.to_regex());
+                assert!(
+                    find_glob_matches(&m.to_glob(), path, m.ignore_case()),
+                    "{query}: {} vs {path}",
+                    m.to_glob()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fuzzy_abbreviation() {
+        let found = matcher("ccdpk").score("/home/me/src/ccd-pick").unwrap();
+        assert_eq!(highlighted("/home/me/src/ccd-pick", &found), "ccdpk");
+        assert!(matcher("ccdpk").score("/home/me/src/pick-ccd").is_none());
+    }
+
+    #[test]
+    fn prefers_boundaries_and_basename() {
+        let m = matcher("src");
+        let basename = m.score("/home/me/projects/src").unwrap();
+        let scattered = m.score("/home/me/scratch/rc").unwrap();
+        assert!(basename.score > scattered.score);
+    }
+
+    #[test]
+    fn non_ascii_positions_are_char_indices() {
+        let path = "/home/jürgen/Ünïcode-dåta";
+        let found = matcher("ünïdåt").score(path).unwrap();
+        assert_eq!(highlighted(path, &found).to_lowercase(), "ünïdåt");
+    }
+
+    #[test]
+    fn external_patterns_cover_fuzzy_queries() {
+        assert_external_patterns_cover(&["ccdpk"]);
+    }
+
+    #[test]
+    fn all_terms_must_match() {
+        let m = matcher("proj web");
+        assert!(m.is_match("/home/me/projects/website"));
+        assert!(m.is_match("/home/me/web/project"));
+        assert!(!m.is_match("/home/me/projects/api"));
+    }
+
+    #[test]
+    fn negated_terms_exclude() {
+        let m = matcher("src !old");
+        assert!(m.is_match("/home/me/src"));
+        assert!(!m.is_match("/home/me/old/src"));
+        // Negation is literal, not fuzzy
+        assert!(m.is_match("/home/me/o/l/d/src"));
+        let found = m.score("/home/me/src").unwrap();
+        assert_eq!(highlighted("/home/me/src", &found), "src");
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(matcher("^/srv").is_match("/srv/www"));
+        assert!(!matcher("^/srv").is_match("/home/srv"));
+        assert!(matcher("src$").is_match("/home/me/src"));
+        assert!(!matcher("src$").is_match("/home/me/src/bin"));
+        assert!(matcher("^/srv$").is_match("/srv"));
+        assert!(!matcher("^/srv$").is_match("/srv/www"));
+        let found = matcher("src$").score("/home/src/x/src").unwrap();
+        assert_eq!(found.positions, vec![12, 13, 14]);
+    }
+
+    #[test]
+    fn basename_terms() {
+        let m = matcher("web/");
+        assert!(m.is_match("/home/me/website"));
+        assert!(!m.is_match("/home/me/website/docs"));
+        assert!(matcher("^web/").is_match("/srv/website"));
+        assert!(!matcher("^web/").is_match("/srv/my-website"));
+        let found = m.score("/web/my-website").unwrap();
+        assert!(found.positions.iter().all(|&i| i >= 5));
+        assert!(matcher("rgen/")
+            .score("/home/jürgen/Ünïcode-dåta")
+            .is_none());
+    }
+
+    #[test]
+    fn external_patterns_cover_terms() {
+        assert_external_patterns_cover(&[
+            "src !old",
+            "^/home/me",
+            "src$",
+            "web/",
+            "^web/",
+            "proj web",
+        ]);
+    }
+
+    #[test]
+    fn segments_match_component_prefixes() {
+        let m = matcher("w/cc/src");
+        let found = m.score("/home/me/work/ccd/src").unwrap();
+        assert_eq!(highlighted("/home/me/work/ccd/src", &found), "wccsrc");
+        assert!(m.is_match("/home/me/work/other/ccd/src"));
+        assert!(!m.is_match("/home/me/web/accounts/src"));
+        assert!(!matcher("w/cc/src/").is_match("/home/me/work/ccd/src/bin"));
+        assert!(matcher("w/cc/src/").is_match("/home/me/work/ccd/src"));
+        let path = "/home/jürgen/Ünïcode-dåta";
+        let found = matcher("j/ün").score(path).unwrap();
+        assert_eq!(highlighted(path, &found), "jÜn");
+    }
+
+    #[test]
+    fn adjacent_segments_rank_higher() {
+        let m = matcher("w/src");
+        let adjacent = m.score("/home/me/work/src").unwrap();
+        let skipping = m.score("/home/me/work/ccd/src").unwrap();
+        assert!(adjacent.score > skipping.score);
+    }
+
+    #[test]
+    fn external_patterns_cover_segments() {
+        assert_external_patterns_cover(&["w/cc/src", "w/cc/src/"]);
+    }
+
+    #[test]
+    fn globs() {
+        let m = matcher("glob:proj*/src");
+        assert!(m.is_match("/home/me/projects/src"));
+        assert!(!m.is_match("/home/me/projects/src/bin"));
+        assert!(matcher("glob:/srv/*/logs").is_match("/srv/www/logs"));
+        assert!(!matcher("glob:/srv/*/logs").is_match("/old/srv/www/logs"));
+        assert!(matcher("glob:fz/**/src").is_match("/tmp/fz/src"));
+        assert!(matcher("glob:fz/**/src").is_match("/tmp/fz/a/b/src"));
+        assert!(matcher("glob:[cs]*/[!d]*").is_match("/x/cache/bin"));
+        assert!(!matcher("glob:[cs]*/[!d]*").is_match("/x/cache/docs"));
+    }
+
+    #[test]
+    fn regexes() {
+        let m = matcher("re:/(src|lib)$");
+        assert!(m.is_match("/home/me/lib"));
+        assert!(!m.is_match("/home/me/libs"));
+        let path = "/home/jürgen/Ünïcode-dåta";
+        let found = matcher("re:dåta$").score(path).unwrap();
+        assert_eq!(highlighted(path, &found), "dåta");
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        assert!(matches!(
+            Matcher::new("re:(", CaseMode::Smart),
+            Err(CddError::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn external_patterns_cover_globs_and_regexes() {
+        assert_external_patterns_cover(&[
+            "glob:proj*/src",
+            "glob:/home/*/work/**",
+            "glob:fz/**/src",
+            "glob:[cs]*/s?c",
+            "re:/(src|lib)$",
+        ]);
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(matcher("proj").is_match("/home/me/Projects"));
+        assert!(!matcher("Proj").is_match("/home/me/projects"));
+        assert!(matcher("Proj").is_match("/home/me/Projects"));
+        // Escapes in regular expressions aren't uppercase letters
+        assert!(matcher(r"re:\Wproj").is_match("/home/me/Projects"));
+        let sensitive = Matcher::new("proj", CaseMode::Sensitive).unwrap();
+        assert!(!sensitive.is_match("/home/me/Projects"));
+        let insensitive = Matcher::new("PROJ", CaseMode::Insensitive).unwrap();
+        assert!(insensitive.is_match("/home/me/projects"));
+        assert_external_patterns_cover(&["Proj"]);
+    }
+
+    #[test]
+    fn rust_only_regex_syntax_isnt_given_to_locate() {
+        assert_eq!(matcher("re:/(src|lib)$").to_posix_regex(), "/(src|lib)$");
+        assert_eq!(matcher(r"re:/v\.[0-9]+$").to_posix_regex(), r"/v\.[0-9]+$");
+        for query in [
+            r"re:\d+$",
+            r"re:(?:src|lib)$",
+            "re:(?i)src",
+            r"re:\bsrc",
+            "re:s.+?c",
+        ] {
+            assert_eq!(matcher(query).to_posix_regex(), "/", "{query}");
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Shells the ccd wrapper function can be installed into
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{
+    CddError, CCD_FISH_FUNCTION, CCD_FISH_TRACK_HOOK, CCD_NUSHELL_FUNCTION, CCD_NUSHELL_TRACK_HOOK,
+    CCD_SHELL_FUNCTION, CCD_TRACK_HOOK, CCD_ZSH_FUNCTION,
+};
+
+// Placeholder for the command name in the wrapper templates
+const COMMAND_PLACEHOLDER: &str = "{{cmd}}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+}
+
+// Whether `init` adds the hook recording every directory change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    Prompt,
+    None,
+}
+
+impl Hook {
+    pub fn parse(name: &str) -> Result<Self, CddError> {
+        match name {
+            "prompt" => Ok(Hook::Prompt),
+            "none" => Ok(Hook::None),
+            _ => Err(CddError::Config(format!(
+                "unknown hook '{name}' (expected prompt or none)"
+            ))),
+        }
+    }
+}
+
+impl Shell {
+    pub fn parse(name: &str) -> Result<Self, CddError> {
+        Self::from_program(name).ok_or_else(|| {
+            CddError::Config(format!(
+                "unsupported shell '{name}' (expected bash, zsh, fish or nushell)"
+            ))
+        })
+    }
+
+    // Recognize a shell from a program name or path such as "/bin/zsh" or "-bash" (login shells)
+    fn from_program(program: &str) -> Option<Self> {
+        let name = Path::new(program.trim())
+            .file_name()?
+            .to_str()?
+            .trim_start_matches('-');
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "nu" | "nushell" => Some(Shell::Nushell),
+            _ => None,
+        }
+    }
+
+    // The shell ccd-pick was started from, falling back to the login shell in $SHELL when the
+    // parent process isn't a supported shell (e.g. when run from a script or `sudo`)
+    pub fn detect() -> Result<Self, CddError> {
+        if let Some(shell) = Self::parent_program().and_then(|name| Self::from_program(&name)) {
+            return Ok(shell);
+        }
+        let login_shell = env::var("SHELL").unwrap_or_default();
+        Self::from_program(&login_shell).ok_or_else(|| {
+            let login_shell = if login_shell.is_empty() {
+                "unknown"
+            } else {
+                &login_shell
+            };
+            CddError::Config(format!(
+                "cannot tell which shell to use (your login shell is {login_shell}); \
+                 pass it explicitly, e.g. --install zsh"
+            ))
+        })
+    }
+
+    fn parent_program() -> Option<String> {
+        let parent = std::os::unix::process::parent_id();
+        // Linux exposes the name directly; elsewhere, ask ps
+        if let Ok(name) = fs::read_to_string(format!("/proc/{parent}/comm")) {
+            return Some(name.trim().to_string());
+        }
+        let output = Command::new("ps")
+            .args(["-o", "comm=", "-p"])
+            .arg(parent.to_string())
+            .output()
+            .ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Nushell => "nushell",
+        }
+    }
+
+    // Generate the wrapper function named `cmd`, followed by the tracking hook with Hook::Prompt
+    pub fn init_script(self, cmd: &str, hook: Hook) -> String {
+        let templates: &[&str] = match hook {
+            Hook::Prompt => &[self.function(), self.track_hook()],
+            Hook::None => &[self.function()],
+        };
+        let mut script = String::new();
+        for template in templates {
+            // Skip the first line (shebang) and keep the rest
+            if let Some(pos) = template.find('\n') {
+                script.push_str(&template[pos + 1..].replace(COMMAND_PLACEHOLDER, cmd));
+                script.push('\n');
+            }
+        }
+        script
+    }
+
+    // The command becomes a function name in every shell, so keep it to a portable set of
+    // characters; naming it ccd-pick would make the wrapper call itself
+    pub fn validate_command_name(name: &str) -> Result<(), CddError> {
+        let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+        if !valid {
+            return Err(CddError::Config(format!(
+                "invalid command name '{name}' (use letters, digits, '-' and '_', starting with a letter)"
+            )));
+        }
+        if name == "ccd-pick" {
+            return Err(CddError::Config(
+                "the command cannot be named ccd-pick, which it runs".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // The wrapper function template, including its shebang line
+    fn function(self) -> &'static str {
+        match self {
+            Shell::Bash => CCD_SHELL_FUNCTION,
+            Shell::Zsh => CCD_ZSH_FUNCTION,
+            Shell::Fish => CCD_FISH_FUNCTION,
+            Shell::Nushell => CCD_NUSHELL_FUNCTION,
+        }
+    }
+
+    // The hook recording every directory change, including its shebang line
+    fn track_hook(self) -> &'static str {
+        match self {
+            Shell::Bash | Shell::Zsh => CCD_TRACK_HOOK,
+            Shell::Fish => CCD_FISH_TRACK_HOOK,
+            Shell::Nushell => CCD_NUSHELL_TRACK_HOOK,
+        }
+    }
+
+    // The startup file line loading the output of `ccd-pick <printfn>`
+    pub fn load_command(self, printfn: &str) -> Result<String, CddError> {
+        match self {
+            Shell::Bash | Shell::Zsh => Ok(format!("eval \"$(ccd-pick {printfn})\"")),
+            Shell::Fish => Ok(format!("ccd-pick {printfn} | source")),
+            Shell::Nushell => Err(no_nushell_install()),
+        }
+    }
+
+    // The startup file --install adds the function to. fish gets a file of its own in conf.d,
+    // which is sourced at startup (unlike functions/, which is only autoloaded on first use and
+    // so would miss the tracking hook).
+    pub fn rc_file(self) -> Result<PathBuf, CddError> {
+        let home = env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .ok_or_else(|| CddError::Config("HOME environment variable not set".to_string()))?;
+        Ok(match self {
+            Shell::Bash => Path::new(&home).join(".bashrc"),
+            Shell::Zsh => env::var_os("ZDOTDIR")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(&home))
+                .join(".zshrc"),
+            Shell::Fish => env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+                .unwrap_or_else(|| Path::new(&home).join(".config"))
+                .join("fish/conf.d/ccd.fish"),
+            Shell::Nushell => return Err(no_nushell_install()),
+        })
+    }
+}
+
+// nushell can only `source` files that exist when its config is parsed
+fn no_nushell_install() -> CddError {
+    CddError::Config(
+        "ccd-pick cannot install into nushell's config; save the output of \
+         `ccd-pick init nushell` to a file and source it from config.nu"
+            .to_string(),
+    )
+}
@@ -0,0 +1,592 @@
+// JSON, CSV and TSV copies of the frequency database for `--export` and `--import-file`,
+// independent of the on-disk format used by FrequencyManager, and merging of databases
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::{escape_field, unescape_field, CddError, FrequencyEntry, FrequencyMap};
+
+// Version of the JSON document layout
+const EXPORT_VERSION: u64 = 1;
+const COLUMNS: [&str; 3] = ["path", "count", "last_access"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Result<Self, CddError> {
+        match name {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            "tsv" => Ok(ExportFormat::Tsv),
+            other => Err(CddError::Config(format!(
+                "unknown format '{other}' (expected json, csv or tsv)"
+            ))),
+        }
+    }
+
+    // Guess the format from a file name such as "backup.csv"
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        Self::parse(&extension).ok()
+    }
+}
+
+// Entries sorted by count, most used first, so exports are stable and easy to skim
+fn sorted_entries(frequency_map: &FrequencyMap) -> Vec<(&String, &FrequencyEntry)> {
+    let mut entries: Vec<_> = frequency_map.iter().collect();
+    entries.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+    entries
+}
+
+// CSV and TSV have one row per directory; metadata is only kept in JSON
+pub fn export(frequency_map: &FrequencyMap, format: ExportFormat) -> String {
+    let entries = sorted_entries(frequency_map);
+    let mut output = String::new();
+
+    match format {
+        ExportFormat::Json => {
+            let directories: Vec<Value> = entries
+                .iter()
+                .map(|(path, entry)| {
+                    json!({
+                        "path": path,
+                        "count": entry.count,
+                        "last_access": entry.last_access,
+                        "metadata": entry.metadata,
+                    })
+                })
+                .collect();
+            let document = json!({ "version": EXPORT_VERSION, "directories": directories });
+            // Serializing a Value can't fail
+            output = serde_json::to_string_pretty(&document).unwrap_or_default();
+            output.push('\n');
+        }
+        ExportFormat::Csv => {
+            output.push_str(&COLUMNS.join(","));
+            output.push('\n');
+            for (path, entry) in entries {
+                output.push_str(&format!(
+                    "{},{},{}\n",
+                    csv_quote(path),
+                    entry.count,
+                    entry.last_access
+                ));
+            }
+        }
+        ExportFormat::Tsv => {
+            output.push_str(&COLUMNS.join("\t"));
+            output.push('\n');
+            for (path, entry) in entries {
+                output.push_str(&format!(
+                    "{}\t{}\t{}\n",
+                    escape_field(path),
+                    entry.count,
+                    entry.last_access
+                ));
+            }
+        }
+    }
+
+    output
+}
+
+// Parse and validate an exported document. Nothing is returned unless every record is valid.
+pub fn import(content: &str, format: ExportFormat) -> Result<FrequencyMap, CddError> {
+    let records = match format {
+        ExportFormat::Json => read_json(content)?,
+        ExportFormat::Csv => read_table(parse_csv(content)?)?,
+        ExportFormat::Tsv => {
+            let rows = content
+                .lines()
+                .filter(|line| !line.is_empty())
+                .enumerate()
+                .map(|(index, line)| {
+                    line.split('\t')
+                        .map(|field| {
+                            unescape_field(field).ok_or_else(|| {
+                                CddError::Import(format!("line {}: invalid escape", index + 1))
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            read_table(rows)?
+        }
+    };
+
+    let mut frequency_map = FrequencyMap::new();
+    let mut seen = HashSet::new();
+    for (number, path, entry) in records {
+        if !seen.insert(path.clone()) {
+            return Err(CddError::Import(format!(
+                "record {number}: duplicate path '{path}'"
+            )));
+        }
+        frequency_map.insert(path, entry);
+    }
+    Ok(frequency_map)
+}
+
+fn invalid(number: usize, what: &str) -> CddError {
+    CddError::Import(format!("record {number}: {what}"))
+}
+
+fn validate_path(number: usize, path: &str) -> Result<(), CddError> {
+    if !path.starts_with('/') {
+        return Err(invalid(number, "path must be absolute"));
+    }
+    Ok(())
+}
+
+fn validate_count(number: usize, count: u64) -> Result<u32, CddError> {
+    match u32::try_from(count) {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(invalid(number, "count must be a positive integer")),
+    }
+}
+
+type Record = (usize, String, FrequencyEntry);
+
+fn read_json(content: &str) -> Result<Vec<Record>, CddError> {
+    let document: Value = serde_json::from_str(content)
+        .map_err(|e| CddError::Import(format!("invalid JSON: {e}")))?;
+
+    let version = document.get("version").and_then(Value::as_u64);
+    match version {
+        Some(version) if version > EXPORT_VERSION => {
+            return Err(CddError::Import(format!(
+                "export version {version} is newer than this ccd supports ({EXPORT_VERSION})"
+            )));
+        }
+        Some(_) => {}
+        None => return Err(CddError::Import("missing \"version\"".to_string())),
+    }
+    let directories = document
+        .get("directories")
+        .and_then(Value::as_array)
+        .ok_or_else(|| CddError::Import("missing \"directories\" array".to_string()))?;
+
+    directories
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let number = index + 1;
+            let path = record
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid(number, "missing \"path\""))?;
+            validate_path(number, path)?;
+            let count = record
+                .get("count")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| invalid(number, "count must be a positive integer"))?;
+            let last_access = match record.get("last_access") {
+                None | Some(Value::Null) => 0,
+                Some(value) => value
+                    .as_u64()
+                    .ok_or_else(|| invalid(number, "last_access must be a Unix timestamp"))?,
+            };
+
+            let mut entry = FrequencyEntry::new(validate_count(number, count)?, last_access);
+            if let Some(metadata) = record.get("metadata").filter(|value| !value.is_null()) {
+                let metadata = metadata
+                    .as_object()
+                    .ok_or_else(|| invalid(number, "metadata must be an object"))?;
+                for (key, value) in metadata {
+                    let value = value
+                        .as_str()
+                        .ok_or_else(|| invalid(number, "metadata values must be strings"))?;
+                    entry.metadata.insert(key.clone(), value.to_string());
+                }
+            }
+            Ok((number, path.to_string(), entry))
+        })
+        .collect()
+}
+
+// CSV and TSV: a header row naming the columns, in any order, then one row per directory
+fn read_table(rows: Vec<Vec<String>>) -> Result<Vec<Record>, CddError> {
+    let mut rows = rows.into_iter();
+    let header = rows
+        .next()
+        .ok_or_else(|| CddError::Import("missing header row".to_string()))?;
+    let column = |name: &str| header.iter().position(|column| column.trim() == name);
+    let (Some(path_column), Some(count_column)) = (column("path"), column("count")) else {
+        return Err(CddError::Import(
+            "header must name at least the path and count columns".to_string(),
+        ));
+    };
+    let last_access_column = column("last_access");
+
+    rows.enumerate()
+        .map(|(index, row)| {
+            let number = index + 1;
+            let field = |column: usize| row.get(column).map(String::as_str).unwrap_or("");
+
+            let path = field(path_column);
+            validate_path(number, path)?;
+            let count = field(count_column)
+                .trim()
+                .parse()
+                .map_err(|_| invalid(number, "count must be a positive integer"))?;
+            let last_access = match last_access_column.map(field).map(str::trim) {
+                None | Some("") => 0,
+                Some(value) => value
+                    .parse()
+                    .map_err(|_| invalid(number, "last_access must be a Unix timestamp"))?,
+            };
+
+            let entry = FrequencyEntry::new(validate_count(number, count)?, last_access);
+            Ok((number, path.to_string(), entry))
+        })
+        .collect()
+}
+
+// Quote a CSV field when it contains a separator, quote or line break (RFC 4180)
+fn csv_quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn parse_csv(content: &str) -> Result<Vec<Vec<String>>, CddError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                // Skip blank lines
+                if row.iter().any(|field| !field.is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                } else {
+                    row.clear();
+                }
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(CddError::Import("unterminated quoted field".to_string()));
+    }
+    row.push(field);
+    if row.iter().any(|field| !field.is_empty()) {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    // Add counts together, for histories of separate use (e.g. different machines)
+    Sum,
+    // Keep the larger count, for copies of the same history that may overlap
+    Max,
+}
+
+impl MergeStrategy {
+    pub fn parse(name: &str) -> Result<Self, CddError> {
+        match name {
+            "sum" => Ok(MergeStrategy::Sum),
+            "max" => Ok(MergeStrategy::Max),
+            other => Err(CddError::Config(format!(
+                "unknown merge strategy '{other}' (expected sum or max)"
+            ))),
+        }
+    }
+}
+
+// Moves paths under one directory to another, e.g. "/home/erik=/Users/erik"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewriteRule {
+    from: String,
+    to: String,
+}
+
+impl RewriteRule {
+    pub fn parse(rule: &str) -> Result<Self, CddError> {
+        let (from, to) = rule
+            .split_once('=')
+            .filter(|(from, to)| from.starts_with('/') && to.starts_with('/'))
+            .ok_or_else(|| {
+                CddError::Config(format!(
+                    "invalid rewrite rule '{rule}' (expected /old/prefix=/new/prefix)"
+                ))
+            })?;
+        // Keep "/" itself, but otherwise compare without trailing slashes
+        let trim = |prefix: &str| match prefix.trim_end_matches('/') {
+            "" => "/".to_string(),
+            trimmed => trimmed.to_string(),
+        };
+        Ok(Self {
+            from: trim(from),
+            to: trim(to),
+        })
+    }
+
+    // Only whole path components match, so /home/erik doesn't rewrite /home/erika
+    fn apply(&self, path: &str) -> Option<String> {
+        let rest = path.strip_prefix(self.from.trim_end_matches('/'))?;
+        let rest = match rest {
+            "" | "/" => return Some(self.to.clone()),
+            rest => rest.strip_prefix('/')?,
+        };
+        Some(format!("{}/{rest}", self.to.trim_end_matches('/')))
+    }
+}
+
+// Apply the first matching rule. Returns None when no rule matches.
+pub fn rewrite_path(rules: &[RewriteRule], path: &str) -> Option<String> {
+    rules.iter().find_map(|rule| rule.apply(path))
+}
+
+// Fold `other` into `entry`: counts are combined by `strategy`, the later access time wins
+// and metadata from `other` overrides existing keys
+pub fn combine(entry: &mut FrequencyEntry, other: FrequencyEntry, strategy: MergeStrategy) {
+    entry.count = match strategy {
+        MergeStrategy::Sum => entry.count.saturating_add(other.count),
+        MergeStrategy::Max => entry.count.max(other.count),
+    };
+    entry.last_access = entry.last_access.max(other.last_access);
+    entry.metadata.extend(other.metadata);
+}
+
+// Merge imported entries into the database. Returns the number of (added, merged) entries.
+pub fn merge(
+    frequency_map: &mut FrequencyMap,
+    imported: FrequencyMap,
+    strategy: MergeStrategy,
+) -> (usize, usize) {
+    let (mut added, mut merged) = (0, 0);
+    for (path, imported) in imported {
+        match frequency_map.get_mut(&path) {
+            Some(entry) => {
+                combine(entry, imported, strategy);
+                merged += 1;
+            }
+            None => {
+                frequency_map.insert(path, imported);
+                added += 1;
+            }
+        }
+    }
+    (added, merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn csv_quoted_fields() {
+        let rows = parse_csv("path,count\n\"/a,b\",1\n\"/say \"\"hi\"\"\",2\n\"/two\nlines\",3\n")
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                row(&["path", "count"]),
+                row(&["/a,b", "1"]),
+                row(&["/say \"hi\"", "2"]),
+                row(&["/two\nlines", "3"]),
+            ]
+        );
+        assert!(matches!(
+            parse_csv("path,count\n\"/open,1\n"),
+            Err(CddError::Import(_))
+        ));
+    }
+
+    #[test]
+    fn csv_line_endings_and_blank_lines() {
+        let rows = parse_csv("path,count\r\n\r\n/a,1\r\n\n/b,2").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                row(&["path", "count"]),
+                row(&["/a", "1"]),
+                row(&["/b", "2"])
+            ]
+        );
+        // A quoted line break is kept as is
+        let rows = parse_csv("path\r\n\"/a\r\nb\"\r\n").unwrap();
+        assert_eq!(rows, vec![row(&["path"]), row(&["/a\r\nb"])]);
+    }
+
+    #[test]
+    fn tsv_fields_are_unescaped() {
+        let content = "path\tcount\tlast_access\n/tab\\there\t2\t100\n/back\\\\slash\t1\t\n";
+        let frequency_map = import(content, ExportFormat::Tsv).unwrap();
+        assert_eq!(frequency_map["/tab\there"], FrequencyEntry::new(2, 100));
+        assert_eq!(frequency_map["/back\\slash"], FrequencyEntry::new(1, 0));
+        assert!(matches!(
+            import("path\tcount\n/bad\\q\t1\n", ExportFormat::Tsv),
+            Err(CddError::Import(_))
+        ));
+    }
+
+    #[test]
+    fn exports_round_trip() {
+        let mut frequency_map = FrequencyMap::new();
+        for (i, path) in [
+            "/plain",
+            "/a,b",
+            "/say \"hi\"",
+            "/tab\there",
+            "/new\nline",
+            "/back\\slash",
+        ]
+        .iter()
+        .enumerate()
+        {
+            frequency_map.insert(path.to_string(), FrequencyEntry::new(i as u32 + 1, 100));
+        }
+        frequency_map
+            .get_mut("/plain")
+            .unwrap()
+            .metadata
+            .insert("tag".to_string(), "work".to_string());
+
+        for format in [ExportFormat::Json, ExportFormat::Csv, ExportFormat::Tsv] {
+            let imported = import(&export(&frequency_map, format), format).unwrap();
+            assert_eq!(imported.len(), frequency_map.len(), "{format:?}");
+            for (path, entry) in &frequency_map {
+                assert_eq!(imported[path].count, entry.count, "{format:?} {path:?}");
+                assert_eq!(imported[path].last_access, entry.last_access, "{format:?}");
+            }
+        }
+        let imported = import(
+            &export(&frequency_map, ExportFormat::Json),
+            ExportFormat::Json,
+        );
+        assert_eq!(imported.unwrap(), frequency_map);
+    }
+
+    #[test]
+    fn invalid_records_are_rejected() {
+        for content in [
+            "path,count\nrelative,1\n",
+            "path,count\n/a,0\n",
+            "path,count\n/a,x\n",
+            "path,count\n/a,1\n/a,2\n",
+            "count\n1\n",
+        ] {
+            assert!(
+                matches!(import(content, ExportFormat::Csv), Err(CddError::Import(_))),
+                "{content:?}"
+            );
+        }
+    }
+
+    fn rewrite(rule: &str, path: &str) -> Option<String> {
+        RewriteRule::parse(rule).unwrap().apply(path)
+    }
+
+    #[test]
+    fn rewrite_rules_match_whole_components() {
+        let rule = "/home/erik=/Users/erik";
+        assert_eq!(rewrite(rule, "/home/erik").as_deref(), Some("/Users/erik"));
+        assert_eq!(
+            rewrite(rule, "/home/erik/src").as_deref(),
+            Some("/Users/erik/src")
+        );
+        assert_eq!(rewrite(rule, "/home/erika"), None);
+        assert_eq!(rewrite(rule, "/home/erika/src"), None);
+        assert_eq!(rewrite(rule, "/srv/home/erik"), None);
+    }
+
+    #[test]
+    fn rewrite_rules_ignore_trailing_slashes() {
+        for rule in ["/home/erik/=/Users/erik/", "/home/erik=/Users/erik/"] {
+            assert_eq!(rewrite(rule, "/home/erik/").as_deref(), Some("/Users/erik"));
+            assert_eq!(
+                rewrite(rule, "/home/erik/src").as_deref(),
+                Some("/Users/erik/src")
+            );
+            assert_eq!(rewrite(rule, "/home/erika"), None);
+        }
+    }
+
+    #[test]
+    fn rewrite_rules_on_the_root() {
+        assert_eq!(rewrite("/=/mnt/old", "/").as_deref(), Some("/mnt/old"));
+        assert_eq!(
+            rewrite("/=/mnt/old", "/home/erik").as_deref(),
+            Some("/mnt/old/home/erik")
+        );
+        assert_eq!(rewrite("/mnt/old=/", "/mnt/old").as_deref(), Some("/"));
+        assert_eq!(
+            rewrite("/mnt/old=/", "/mnt/old/home").as_deref(),
+            Some("/home")
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = [
+            RewriteRule::parse("/home/erik/work=/work").unwrap(),
+            RewriteRule::parse("/home/erik=/Users/erik").unwrap(),
+        ];
+        assert_eq!(
+            rewrite_path(&rules, "/home/erik/work/ccd").as_deref(),
+            Some("/work/ccd")
+        );
+        assert_eq!(
+            rewrite_path(&rules, "/home/erik/src").as_deref(),
+            Some("/Users/erik/src")
+        );
+        assert_eq!(rewrite_path(&rules, "/srv"), None);
+    }
+
+    #[test]
+    fn invalid_rewrite_rules() {
+        for rule in ["/home/erik", "home/erik=/Users/erik", "/home/erik=Users"] {
+            assert!(
+                matches!(RewriteRule::parse(rule), Err(CddError::Config(_))),
+                "{rule}"
+            );
+        }
+    }
+
+    #[test]
+    fn merging_combines_counts_by_strategy() {
+        let mut ours = FrequencyMap::new();
+        ours.insert("/a".to_string(), FrequencyEntry::new(3, 100));
+        let mut theirs = FrequencyMap::new();
+        theirs.insert("/a".to_string(), FrequencyEntry::new(5, 50));
+        theirs.insert("/b".to_string(), FrequencyEntry::new(1, 10));
+
+        let mut summed = ours.clone();
+        assert_eq!(
+            merge(&mut summed, theirs.clone(), MergeStrategy::Sum),
+            (1, 1)
+        );
+        assert_eq!(summed["/a"], FrequencyEntry::new(8, 100));
+        assert_eq!(merge(&mut ours, theirs, MergeStrategy::Max), (1, 1));
+        assert_eq!(ours["/a"], FrequencyEntry::new(5, 100));
+    }
+}
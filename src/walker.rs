@@ -0,0 +1,226 @@
+// Built-in filesystem walker, used when `locate` is missing or its database is stale
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use crate::exclude::Exclusions;
+use crate::matcher::Matcher;
+
+// Directory names and absolute paths that are never descended into by default
+const DEFAULT_IGNORE: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    "node_modules",
+    "/proc",
+    "/sys",
+    "/dev",
+    "/run",
+];
+const DEFAULT_MAX_DEPTH: usize = 12;
+
+#[derive(Debug, Clone)]
+pub struct WalkerConfig {
+    pub roots: Vec<PathBuf>,
+    pub max_depth: usize,
+    // Entries starting with '/' match a full path, everything else matches a directory name
+    pub ignore: Vec<String>,
+    // Excluded directories are neither reported nor descended into
+    pub exclude: Exclusions,
+}
+
+impl WalkerConfig {
+    pub fn new(home: Option<&str>, exclude: Exclusions) -> Self {
+        let root = home.unwrap_or("/");
+        Self {
+            roots: vec![PathBuf::from(root)],
+            max_depth: DEFAULT_MAX_DEPTH,
+            ignore: DEFAULT_IGNORE.iter().map(|s| s.to_string()).collect(),
+            exclude,
+        }
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        self.ignore.iter().any(|rule| {
+            if rule.starts_with('/') {
+                path == Path::new(rule)
+            } else {
+                name == rule
+            }
+        }) || self.exclude.is_excluded(&path.to_string_lossy())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WalkResult {
+    pub directories: Vec<String>,
+    pub files_matched: usize,
+    // Set when the walk stopped at the limit, to continue it with `resume`
+    pub rest: Option<WalkRest>,
+}
+
+// Where a walk stopped: the directories it has yet to read, and the matches it found past
+// the limit
+#[derive(Debug, Default)]
+pub struct WalkRest {
+    queue: VecDeque<(PathBuf, usize)>,
+    directories: Vec<String>,
+}
+
+// Shared state for the worker threads
+struct WalkState {
+    queue: VecDeque<(PathBuf, usize)>,
+    active: usize,
+    result: WalkResult,
+}
+
+// Walk the configured roots in parallel, collecting directories whose path matches
+// `matcher` and counting matching files. Stops once `limit` directories have been found,
+// or early when `cancel` is set.
+pub fn walk(
+    matcher: &Matcher,
+    config: &WalkerConfig,
+    limit: usize,
+    cancel: &AtomicBool,
+) -> WalkResult {
+    let queue = config
+        .roots
+        .iter()
+        .filter(|root| root.is_dir())
+        .map(|root| (root.clone(), 0))
+        .collect();
+    let rest = WalkRest {
+        queue,
+        directories: Vec::new(),
+    };
+    resume(matcher, config, rest, limit, cancel)
+}
+
+// Continue a walk where it stopped, collecting up to `limit` more directories
+pub fn resume(
+    matcher: &Matcher,
+    config: &WalkerConfig,
+    rest: WalkRest,
+    limit: usize,
+    cancel: &AtomicBool,
+) -> WalkResult {
+    let done = AtomicBool::new(rest.directories.len() >= limit);
+    let state = Mutex::new(WalkState {
+        queue: rest.queue,
+        active: 0,
+        result: WalkResult {
+            directories: rest.directories,
+            ..WalkResult::default()
+        },
+    });
+    let wakeup = Condvar::new();
+
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(8);
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| worker(matcher, config, limit, &state, &wakeup, &done, cancel));
+        }
+    });
+
+    let Ok(WalkState {
+        queue, mut result, ..
+    }) = state.into_inner()
+    else {
+        return WalkResult::default();
+    };
+    let overflow = result
+        .directories
+        .split_off(limit.min(result.directories.len()));
+    if !queue.is_empty() || !overflow.is_empty() {
+        result.rest = Some(WalkRest {
+            queue,
+            directories: overflow,
+        });
+    }
+    result
+}
+
+fn worker(
+    matcher: &Matcher,
+    config: &WalkerConfig,
+    limit: usize,
+    state: &Mutex<WalkState>,
+    wakeup: &Condvar,
+    done: &AtomicBool,
+    cancel: &AtomicBool,
+) {
+    loop {
+        // Take the next directory off the queue, or wait until another worker adds one
+        let (dir, depth) = {
+            let mut guard = state.lock().unwrap();
+            loop {
+                if done.load(Ordering::Relaxed) || cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Some(item) = guard.queue.pop_front() {
+                    guard.active += 1;
+                    break item;
+                }
+                if guard.active == 0 {
+                    done.store(true, Ordering::Relaxed);
+                    wakeup.notify_all();
+                    return;
+                }
+                guard = wakeup.wait(guard).unwrap();
+            }
+        };
+
+        let mut subdirs = Vec::new();
+        let mut matched_dirs = Vec::new();
+        let mut matched_files = 0;
+
+        if let Ok(entries) = fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let path = entry.path();
+                let path_str = path.to_string_lossy();
+                let is_match = matcher.is_match(&path_str);
+
+                // Symlinked directories are reported but not followed, to avoid cycles
+                if file_type.is_symlink() && path.is_dir() {
+                    if is_match && !config.is_ignored(&path) {
+                        matched_dirs.push(path_str.to_string());
+                    }
+                } else if file_type.is_dir() {
+                    if config.is_ignored(&path) {
+                        continue;
+                    }
+                    if is_match {
+                        matched_dirs.push(path_str.to_string());
+                    }
+                    if depth + 1 < config.max_depth {
+                        subdirs.push((path, depth + 1));
+                    }
+                } else if is_match {
+                    matched_files += 1;
+                }
+            }
+        }
+
+        // Subdirectories are queued even past the limit, so that the walk can resume there
+        let mut guard = state.lock().unwrap();
+        guard.active -= 1;
+        guard.result.directories.extend(matched_dirs);
+        guard.result.files_matched += matched_files;
+        guard.queue.extend(subdirs);
+        if guard.result.directories.len() >= limit {
+            done.store(true, Ordering::Relaxed);
+        }
+        wakeup.notify_all();
+    }
+}
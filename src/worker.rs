@@ -0,0 +1,156 @@
+// Background search worker for the interactive mode, so typing never waits on a search
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::matcher::Matcher;
+use crate::{CddError, DirectorySearcher, FrequencyMap, SearchConfig, SearchResult, SearchSession};
+
+// Wait this long after the last keystroke before starting a search
+const DEBOUNCE: Duration = Duration::from_millis(120);
+
+struct SearchRequest {
+    id: u64,
+    // None to continue the previous search where its backends stopped
+    matcher: Option<Matcher>,
+    limit: usize,
+    frequency_map: FrequencyMap,
+    cancel: Arc<AtomicBool>,
+}
+
+pub struct SearchUpdate {
+    pub result: Result<SearchResult, CddError>,
+    // False for partial results streamed while later backends are still running
+    pub done: bool,
+}
+
+pub struct SearchWorker {
+    requests: Sender<SearchRequest>,
+    updates: Receiver<(u64, SearchUpdate)>,
+    next_id: u64,
+    current: Option<(u64, Arc<AtomicBool>)>,
+}
+
+impl SearchWorker {
+    pub fn spawn(config: SearchConfig) -> Self {
+        let (request_tx, request_rx) = mpsc::channel();
+        let (update_tx, update_rx) = mpsc::channel();
+        thread::spawn(move || run(config, request_rx, update_tx));
+
+        Self {
+            requests: request_tx,
+            updates: update_rx,
+            next_id: 0,
+            current: None,
+        }
+    }
+
+    // Queue a search, cancelling the one in flight
+    pub fn submit(&mut self, matcher: Matcher, limit: usize, frequency_map: &FrequencyMap) {
+        self.send(Some(matcher), limit, frequency_map);
+    }
+
+    // Queue a request for up to `limit` more directories from each backend of the last
+    // search that stopped at the limit
+    pub fn load_more(&mut self, limit: usize, frequency_map: &FrequencyMap) {
+        self.send(None, limit, frequency_map);
+    }
+
+    fn send(&mut self, matcher: Option<Matcher>, limit: usize, frequency_map: &FrequencyMap) {
+        self.cancel();
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.next_id += 1;
+        self.current = Some((self.next_id, Arc::clone(&cancel)));
+
+        let _ = self.requests.send(SearchRequest {
+            id: self.next_id,
+            matcher,
+            limit,
+            frequency_map: frequency_map.clone(),
+            cancel,
+        });
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some((_, cancel)) = self.current.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Updates for the current search that arrived since the last call; stale ones are dropped
+    pub fn poll(&self) -> Vec<SearchUpdate> {
+        let current_id = self.current.as_ref().map(|(id, _)| *id);
+        self.updates
+            .try_iter()
+            .filter(|(id, _)| Some(*id) == current_id)
+            .map(|(_, update)| update)
+            .collect()
+    }
+}
+
+fn run(
+    config: SearchConfig,
+    requests: Receiver<SearchRequest>,
+    updates: Sender<(u64, SearchUpdate)>,
+) {
+    // The last search, kept so that loading more results can continue it
+    let mut session: Option<SearchSession> = None;
+
+    while let Ok(mut request) = requests.recv() {
+        // Debounce: keep taking newer requests until the user pauses typing
+        loop {
+            match requests.recv_timeout(DEBOUNCE) {
+                Ok(newer) => request = newer,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        if request.cancel.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        let id = request.id;
+        let result = match (request.matcher, session.as_mut()) {
+            (None, Some(session)) => {
+                session.more(request.limit, &request.frequency_map, &request.cancel)
+            }
+            (None, None) => Err(CddError::NoDirectoriesFound),
+            (Some(matcher), _) => {
+                // Dropping the last search stops the commands it left waiting
+                session = None;
+                DirectorySearcher::search_streaming(
+                    &matcher,
+                    &request.frequency_map,
+                    &config,
+                    request.limit,
+                    &request.cancel,
+                    |partial| {
+                        let update = SearchUpdate {
+                            result: Ok(partial),
+                            done: false,
+                        };
+                        let _ = updates.send((id, update));
+                    },
+                )
+                .map(|new| {
+                    let result = new.result(&request.frequency_map);
+                    session = Some(new);
+                    result
+                })
+            }
+        };
+
+        if matches!(result, Err(CddError::Cancelled)) {
+            continue;
+        }
+        if updates
+            .send((id, SearchUpdate { result, done: true }))
+            .is_err()
+        {
+            return;
+        }
+    }
+}